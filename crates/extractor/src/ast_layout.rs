@@ -0,0 +1,361 @@
+use super::slot_util::SlotUtil;
+use super::error::ExtractorError;
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+// The storageLayout solc would emit for a contract holding the schema structs, built from the `ast` output alone.
+// Type ids and labels follow solc's (t_struct(<Name>)<astId>_storage, ...); members are packed by Solidity's rules:
+// value types share a slot while they fit, everything else starts a new slot and fills whole slots.
+static SIZED_ELEMENTARY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(uint|int|bytes)(\d+)$").unwrap());
+static ARRAY_LENGTH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[(\d*)\]").unwrap());
+
+pub struct ASTLayout {
+    declarations: HashMap<u64, (String, Value)>, // key=ast id, value=(source unit, struct/enum/contract/UDVT definition)
+    types: Map<String, Value>,
+    visiting: HashMap<String, String>, // key=struct type id being laid out, value=its label
+}
+
+impl ASTLayout {
+    // `base_slots`: key=schema struct name, value=0x-prefixed base slot. Returns {"storage": [...], "types": {...}}
+    pub fn storage_layout(blob: &Value, base_slots: &HashMap<String, String>) -> Result<Value, ExtractorError> {
+        let mut layout = Self { declarations: HashMap::new(), types: Map::new(), visiting: HashMap::new() };
+        if let Some(sources) = blob["sources"].as_object() {
+            for (unit, source) in sources {
+                layout.index(unit, &source["ast"]);
+            }
+        }
+
+        let mut names: Vec<&String> = base_slots.keys().collect();
+        names.sort();
+        let mut storage = Vec::new();
+        for name in names {
+            let (unit, definition) = layout.find_struct(name)?;
+            let type_id = layout.struct_type(&unit, &definition)?;
+            let slot = SlotUtil::slot_to_decimal(&base_slots[name])
+                .map_err(|e| ExtractorError::Layout(format!("Base slot {} of {}: {}", base_slots[name], name, e)))?;
+            storage.push(json!({
+                "astId": definition["id"],
+                "contract": Self::contract(&unit, &definition),
+                "label": name,
+                "offset": 0,
+                "slot": slot,
+                "type": type_id,
+            }));
+        }
+        Ok(json!({ "storage": storage, "types": layout.types }))
+    }
+
+    // Underlying types of the UDVTs declared in the ast, for storageLayout types solc made.
+    // key=t_userDefinedValueType(<Name>)<astId>, value=(underlying type id, its types entry)
+    pub fn udvt_underlying(blob: &Value) -> HashMap<String, (String, Value)> {
+        let mut layout = Self { declarations: HashMap::new(), types: Map::new(), visiting: HashMap::new() };
+        if let Some(sources) = blob["sources"].as_object() {
+            for (unit, source) in sources {
+                layout.index(unit, &source["ast"]);
+            }
+        }
+        layout.declarations.values()
+            .filter(|(_, definition)| definition["nodeType"] == "UserDefinedValueTypeDefinition")
+            .filter_map(|(_, definition)| {
+                let type_id = format!("t_userDefinedValueType({}){}", Self::name(definition).ok()?, definition["id"]);
+                Self::elementary(&definition["underlyingType"]).ok().map(|underlying| (type_id, underlying))
+            })
+            .collect()
+    }
+
+    fn index(&mut self, unit: &str, node: &Value) {
+        let declares_type = ["StructDefinition", "EnumDefinition", "ContractDefinition", "UserDefinedValueTypeDefinition"]
+            .iter().any(|node_type| node["nodeType"] == *node_type);
+        if let (true, Some(id)) = (declares_type, node["id"].as_u64()) {
+            self.declarations.insert(id, (unit.to_string(), node.clone()));
+        }
+        if let Some(children) = node["nodes"].as_array() {
+            for child in children {
+                self.index(unit, child);
+            }
+        }
+    }
+
+    // Note: the schema declares them in `Schema`, so Schema.<Name> wins when another contract reuses the name
+    fn find_struct(&self, name: &str) -> Result<(String, Value), ExtractorError> {
+        let mut candidates: Vec<(&u64, &(String, Value))> = self.declarations.iter()
+            .filter(|(_, (_, node))| node["nodeType"] == "StructDefinition" && node["name"] == name)
+            .collect();
+        candidates.sort_by_key(|(id, _)| **id);
+        let schema_name = format!("Schema.{}", name);
+        match candidates.iter().find(|(_, (_, node))| node["canonicalName"] == schema_name.as_str()).or(candidates.first()) {
+            Some((_, (unit, node))) => Ok((unit.clone(), node.clone())),
+            None => Err(ExtractorError::Layout(format!("No struct {} in the schema AST", name))),
+        }
+    }
+
+    fn struct_type(&mut self, unit: &str, definition: &Value) -> Result<String, ExtractorError> {
+        let type_id = format!("t_struct({}){}_storage", Self::name(definition)?, definition["id"]);
+        // Note: also stops recursion through mappings/dynamic arrays of the struct itself
+        if self.types.contains_key(&type_id) || self.visiting.contains_key(&type_id) {
+            return Ok(type_id);
+        }
+        let label = format!("struct {}", definition["canonicalName"].as_str().unwrap_or(Self::name(definition)?));
+        self.visiting.insert(type_id.clone(), label.clone());
+
+        let mut members = Vec::new();
+        let (mut slot, mut offset) = (0usize, 0usize);
+        for member in definition["members"].as_array().unwrap_or(&Vec::new()) {
+            let member_type = self.type_id(&member["typeName"])?;
+            let number_of_bytes = self.number_of_bytes(&member_type)?;
+            if offset > 0 && offset + number_of_bytes > 32 {
+                slot += 1;
+                offset = 0;
+            }
+            members.push(json!({
+                "astId": member["id"],
+                "contract": Self::contract(unit, definition),
+                "label": member["name"],
+                "offset": offset,
+                "slot": slot.to_string(),
+                "type": member_type,
+            }));
+            offset += number_of_bytes;
+            slot += offset / 32;
+            offset %= 32;
+        }
+        let slots = slot + if offset > 0 { 1 } else { 0 };
+
+        self.visiting.remove(&type_id);
+        self.types.insert(type_id.clone(), json!({
+            "encoding": "inplace",
+            "label": label,
+            "members": members,
+            "numberOfBytes": (slots * 32).to_string(),
+        }));
+        Ok(type_id)
+    }
+
+    // Registers the type a TypeName node stands for (and everything it refers to), returning its id.
+    fn type_id(&mut self, type_name: &Value) -> Result<String, ExtractorError> {
+        let (type_id, entry) = match type_name["nodeType"].as_str().unwrap_or_default() {
+            "ElementaryTypeName" => Self::elementary(type_name)?,
+            "UserDefinedTypeName" => {
+                let id = type_name["referencedDeclaration"].as_u64()
+                    .ok_or(ExtractorError::Layout(format!("{} has no referencedDeclaration", Self::type_string(type_name))))?;
+                let (unit, definition) = self.declarations.get(&id).cloned()
+                    .ok_or(ExtractorError::Layout(format!("Declaration {} of {} is not in the AST", id, Self::type_string(type_name))))?;
+                let name = Self::name(&definition)?;
+                let canonical_name = definition["canonicalName"].as_str().unwrap_or(name);
+                match definition["nodeType"].as_str().unwrap_or_default() {
+                    "StructDefinition" => return self.struct_type(&unit, &definition),
+                    "EnumDefinition" => {
+                        // Note: one byte holds up to 256 variants
+                        let variants = definition["members"].as_array().map(|members| members.len()).unwrap_or(0);
+                        let number_of_bytes = if variants > 256 { 2 } else { 1 };
+                        (format!("t_enum({}){}", name, id), json!({ "encoding": "inplace", "label": format!("enum {}", canonical_name), "numberOfBytes": number_of_bytes.to_string() }))
+                    },
+                    "ContractDefinition" => {
+                        (format!("t_contract({}){}", name, id), json!({ "encoding": "inplace", "label": format!("contract {}", name), "numberOfBytes": "20" }))
+                    },
+                    "UserDefinedValueTypeDefinition" => {
+                        // Note: `underlying` isn't in solc's entry; ValueDecoder reads the value as that type
+                        let (underlying_id, underlying) = Self::elementary(&definition["underlyingType"])?;
+                        let entry = json!({ "encoding": "inplace", "label": canonical_name, "numberOfBytes": underlying["numberOfBytes"], "underlying": underlying_id });
+                        self.types.insert(underlying_id, underlying);
+                        (format!("t_userDefinedValueType({}){}", name, id), entry)
+                    },
+                    other => return Err(ExtractorError::Layout(format!("{} refers to a {}, which has no storage layout", Self::type_string(type_name), other))),
+                }
+            },
+            "Mapping" => {
+                let key = match Self::elementary(&type_name["keyType"]) {
+                    // Note: solc names string/bytes keys after their memory form
+                    Ok((key, entry)) if entry["encoding"] == "bytes" => {
+                        let key = key.replace("_storage", "_memory_ptr");
+                        self.types.insert(key.clone(), entry);
+                        key
+                    },
+                    _ => self.type_id(&type_name["keyType"])?,
+                };
+                let value = self.type_id(&type_name["valueType"])?;
+                let label = format!("mapping({} => {})", self.label(&key)?, self.label(&value)?);
+                (format!("t_mapping({},{})", key, value), json!({ "encoding": "mapping", "key": key, "label": label, "numberOfBytes": "32", "value": value }))
+            },
+            "ArrayTypeName" => {
+                let base = self.type_id(&type_name["baseType"])?;
+                let base_label = self.label(&base)?;
+                match Self::array_length(type_name)? {
+                    None => (format!("t_array({})dyn_storage", base), json!({ "base": base, "encoding": "dynamic_array", "label": format!("{}[]", base_label), "numberOfBytes": "32" })),
+                    Some(length) => {
+                        let base_bytes = self.number_of_bytes(&base)?;
+                        // Note: elements smaller than a slot are packed, the rest take whole slots each
+                        let slots = if base_bytes < 32 { length.div_ceil(32 / base_bytes) } else { length * base_bytes.div_ceil(32) };
+                        (format!("t_array({}){}_storage", base, length), json!({ "base": base, "encoding": "inplace", "label": format!("{}[{}]", base_label, length), "numberOfBytes": (slots * 32).to_string() }))
+                    },
+                }
+            },
+            other => return Err(ExtractorError::Layout(format!("{} ({}) can't be laid out in storage", Self::type_string(type_name), other))),
+        };
+        self.types.insert(type_id.clone(), entry);
+        Ok(type_id)
+    }
+
+    fn elementary(type_name: &Value) -> Result<(String, Value), ExtractorError> {
+        let name = type_name["name"].as_str().unwrap_or_default();
+        let inplace = |type_id: String, label: &str, number_of_bytes: usize| {
+            Ok((type_id, json!({ "encoding": "inplace", "label": label, "numberOfBytes": number_of_bytes.to_string() })))
+        };
+        match name {
+            "bool" => inplace("t_bool".to_string(), "bool", 1),
+            "address" if type_name["stateMutability"] == "payable" => inplace("t_address_payable".to_string(), "address payable", 20),
+            "address" => inplace("t_address".to_string(), "address", 20),
+            "uint" | "int" => inplace(format!("t_{}256", name), &format!("{}256", name), 32),
+            "byte" => inplace("t_bytes1".to_string(), "bytes1", 1),
+            "string" | "bytes" => Ok((format!("t_{}_storage", name), json!({ "encoding": "bytes", "label": name, "numberOfBytes": "32" }))),
+            _ => {
+                match SIZED_ELEMENTARY.captures(name) {
+                    Some(capture) => {
+                        let bits: usize = capture[2].parse().unwrap();
+                        let number_of_bytes = if &capture[1] == "bytes" { bits } else { bits / 8 };
+                        inplace(format!("t_{}", name), name, number_of_bytes)
+                    },
+                    None => Err(ExtractorError::Layout(format!("{} can't be laid out in storage", Self::type_string(type_name)))),
+                }
+            },
+        }
+    }
+
+    // Note: read from the type string rather than the `length` expression, which may be a constant
+    fn array_length(type_name: &Value) -> Result<Option<usize>, ExtractorError> {
+        let type_string = Self::type_string(type_name);
+        match ARRAY_LENGTH.captures_iter(&type_string).last() {
+            Some(capture) if capture[1].is_empty() => Ok(None),
+            Some(capture) => Ok(Some(capture[1].parse().map_err(|_| ExtractorError::Layout(format!("{} has an invalid length", type_string)))?)),
+            None => Err(ExtractorError::Layout(format!("Can't read the length of {}", type_string))),
+        }
+    }
+
+    // Note: a struct that's still being laid out has no size yet, i.e. it contains itself
+    fn number_of_bytes(&self, type_id: &str) -> Result<usize, ExtractorError> {
+        self.types.get(type_id).and_then(|entry| entry["numberOfBytes"].as_str()).and_then(|n| n.parse().ok())
+            .ok_or(ExtractorError::Layout(format!("{} has no numberOfBytes", type_id)))
+    }
+
+    fn label(&self, type_id: &str) -> Result<String, ExtractorError> {
+        if let Some(label) = self.visiting.get(type_id) {
+            return Ok(label.clone());
+        }
+        self.types.get(type_id).and_then(|entry| entry["label"].as_str()).map(|label| label.to_string())
+            .ok_or(ExtractorError::Layout(format!("{} has no label", type_id)))
+    }
+
+    fn name(definition: &Value) -> Result<&str, ExtractorError> {
+        definition["name"].as_str().ok_or(ExtractorError::Layout(format!("AST node {} has no name", definition["id"])))
+    }
+
+    fn type_string(type_name: &Value) -> String {
+        type_name["typeDescriptions"]["typeString"].as_str().unwrap_or_default().to_string()
+    }
+
+    // <source unit>:<contract>, as solc fills the `contract` of layout entries
+    fn contract(unit: &str, definition: &Value) -> String {
+        match definition["canonicalName"].as_str().and_then(|name| name.split_once('.')) {
+            Some((contract, _)) => format!("{}:{}", unit, contract),
+            None => unit.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn elementary(name: &str) -> Value {
+        json!({ "nodeType": "ElementaryTypeName", "name": name, "typeDescriptions": { "typeString": name } })
+    }
+
+    fn user_defined(id: u64, type_string: &str) -> Value {
+        json!({ "nodeType": "UserDefinedTypeName", "referencedDeclaration": id, "typeDescriptions": { "typeString": type_string } })
+    }
+
+    fn member(id: u64, name: &str, type_name: Value) -> Value {
+        json!({ "nodeType": "VariableDeclaration", "id": id, "name": name, "typeName": type_name })
+    }
+
+    #[test]
+    fn test_storage_layout_packs_members() {
+        let main = json!({ "nodeType": "StructDefinition", "id": 10, "name": "Main", "canonicalName": "Schema.Main", "members": [
+            member(11, "a", elementary("uint128")),
+            member(12, "b", elementary("uint128")),
+            member(13, "owner", elementary("address")),
+            member(14, "flag", elementary("bool")),
+            member(15, "kind", user_defined(30, "enum Schema.Kind")),
+            member(16, "votes", json!({ "nodeType": "Mapping", "keyType": elementary("string"), "valueType": user_defined(20, "struct Schema.Sub") })),
+            member(17, "small", json!({ "nodeType": "ArrayTypeName", "baseType": elementary("uint8"), "typeDescriptions": { "typeString": "uint8[40]" } })),
+            member(18, "name", elementary("string")),
+            member(19, "sub", user_defined(20, "struct Schema.Sub")),
+            member(9, "last", elementary("uint8")),
+            member(8, "price", user_defined(40, "Schema.Price")),
+        ]});
+        let sub = json!({ "nodeType": "StructDefinition", "id": 20, "name": "Sub", "canonicalName": "Schema.Sub", "members": [
+            member(21, "x", elementary("uint64")),
+            member(22, "ids", json!({ "nodeType": "ArrayTypeName", "baseType": elementary("uint256"), "typeDescriptions": { "typeString": "uint256[]" } })),
+        ]});
+        let kind = json!({ "nodeType": "EnumDefinition", "id": 30, "name": "Kind", "canonicalName": "Schema.Kind", "members": [{ "name": "A" }, { "name": "B" }] });
+        let price = json!({ "nodeType": "UserDefinedValueTypeDefinition", "id": 40, "name": "Price", "canonicalName": "Schema.Price", "underlyingType": elementary("uint128") });
+        let blob = json!({ "sources": { "src/textDAO/storages/Schema.sol": { "ast": {
+            "nodeType": "SourceUnit",
+            "nodes": [{ "nodeType": "ContractDefinition", "id": 1, "name": "Schema", "nodes": [main, sub, kind, price] }]
+        }}}});
+
+        let layout = ASTLayout::storage_layout(&blob, &HashMap::from([("Main".to_string(), "0x100".to_string())])).unwrap();
+        assert_eq!(layout["storage"], json!([{ "astId": 10, "contract": "src/textDAO/storages/Schema.sol:Schema", "label": "Main", "offset": 0, "slot": "256", "type": "t_struct(Main)10_storage" }]));
+
+        let types = &layout["types"];
+        let positions: Vec<(String, String, u64)> = types["t_struct(Main)10_storage"]["members"].as_array().unwrap().iter()
+            .map(|m| (m["label"].as_str().unwrap().to_string(), m["slot"].as_str().unwrap().to_string(), m["offset"].as_u64().unwrap()))
+            .collect();
+        let expected = [("a", "0", 0), ("b", "0", 16), ("owner", "1", 0), ("flag", "1", 20), ("kind", "1", 21), ("votes", "2", 0), ("small", "3", 0), ("name", "5", 0), ("sub", "6", 0), ("last", "8", 0), ("price", "8", 1)];
+        assert_eq!(positions, expected.iter().map(|(l, s, o)| (l.to_string(), s.to_string(), *o)).collect::<Vec<_>>());
+        assert_eq!(types["t_struct(Main)10_storage"]["numberOfBytes"], "288");
+        assert_eq!(types["t_struct(Main)10_storage"]["label"], "struct Schema.Main");
+
+        assert_eq!(types["t_struct(Sub)20_storage"]["numberOfBytes"], "64");
+        assert_eq!(types["t_mapping(t_string_memory_ptr,t_struct(Sub)20_storage)"], json!({
+            "encoding": "mapping", "key": "t_string_memory_ptr", "label": "mapping(string => struct Schema.Sub)", "numberOfBytes": "32", "value": "t_struct(Sub)20_storage"
+        }));
+        assert_eq!(types["t_array(t_uint8)40_storage"], json!({ "base": "t_uint8", "encoding": "inplace", "label": "uint8[40]", "numberOfBytes": "64" }));
+        assert_eq!(types["t_array(t_uint256)dyn_storage"]["encoding"], "dynamic_array");
+        assert_eq!(types["t_enum(Kind)30"], json!({ "encoding": "inplace", "label": "enum Schema.Kind", "numberOfBytes": "1" }));
+        assert_eq!(types["t_string_storage"], json!({ "encoding": "bytes", "label": "string", "numberOfBytes": "32" }));
+        assert_eq!(types["t_userDefinedValueType(Price)40"], json!({ "encoding": "inplace", "label": "Schema.Price", "numberOfBytes": "16", "underlying": "t_uint128" }));
+        assert_eq!(types["t_uint128"]["label"], "uint128");
+
+        let underlying = ASTLayout::udvt_underlying(&blob);
+        assert_eq!(underlying["t_userDefinedValueType(Price)40"].0, "t_uint128");
+    }
+
+    #[test]
+    fn test_storage_layout_of_recursive_struct() {
+        let node = json!({ "nodeType": "StructDefinition", "id": 10, "name": "Node", "canonicalName": "Schema.Node", "members": [
+            member(11, "children", json!({ "nodeType": "Mapping", "keyType": elementary("uint256"), "valueType": user_defined(10, "struct Schema.Node") })),
+            member(12, "value", elementary("uint256")),
+            member(13, "siblings", json!({ "nodeType": "ArrayTypeName", "baseType": user_defined(10, "struct Schema.Node"), "typeDescriptions": { "typeString": "struct Schema.Node[]" } })),
+        ]});
+        let cycle = json!({ "nodeType": "StructDefinition", "id": 20, "name": "Cycle", "canonicalName": "Schema.Cycle", "members": [
+            member(21, "inner", json!({ "nodeType": "ArrayTypeName", "baseType": user_defined(20, "struct Schema.Cycle"), "typeDescriptions": { "typeString": "struct Schema.Cycle[2]" } })),
+        ]});
+        let blob = json!({ "sources": { "src/textDAO/storages/Schema.sol": { "ast": {
+            "nodeType": "SourceUnit",
+            "nodes": [{ "nodeType": "ContractDefinition", "id": 1, "name": "Schema", "nodes": [node, cycle] }]
+        }}}});
+
+        let layout = ASTLayout::storage_layout(&blob, &HashMap::from([("Node".to_string(), "0x0".to_string())])).unwrap();
+        let types = &layout["types"];
+        assert_eq!(types["t_struct(Node)10_storage"]["numberOfBytes"], "96");
+        assert_eq!(types["t_mapping(t_uint256,t_struct(Node)10_storage)"]["label"], "mapping(uint256 => struct Schema.Node)");
+        assert_eq!(types["t_array(t_struct(Node)10_storage)dyn_storage"]["label"], "struct Schema.Node[]");
+
+        // a struct can't hold itself in place; its size is never taken as zero
+        assert!(ASTLayout::storage_layout(&blob, &HashMap::from([("Cycle".to_string(), "0x0".to_string())])).is_err());
+    }
+}
@@ -1,77 +1,80 @@
 // use super::compiler::Compiler;
 // use super::extractor::Extractor;
 // use super::executor::Executor;
-use super::registry::Registry;
-use super::executable::Executable;
 // use super::perf_config_item::PerfConfigItem;
 use super::type_kind::TypeKind;
+use super::type_id::{TypeId, TypeIdError};
 // use super::eth_call::EthCall;
-use super::perf_expression_evaluator::PerfExpressionEvaluator;
 // use super::ast_node::ASTNode;
 
 
 use std::collections::HashMap;
-use std::process::Command;
+use std::sync::LazyLock;
 use serde_json::Value;
+use regex::Regex;
+
+static ERC7201_LOCATION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"@custom:storage-location\s+erc7201:(\S+)").unwrap());
 
 #[derive(Clone)]
 pub struct ASTNode;
-pub struct ParsedASTType {
-    pub envelope: Option<TypeKind>,
-    pub key_type: Option<String>,
-    pub value_type: String,
-}
 
 impl ASTNode {
-    pub fn type_kind(type_str: &str) -> TypeKind {
-        let parsed_type = ASTNode::parse_type_str(type_str);
-        if let Some(envelope) = parsed_type.envelope {
-            match envelope {
-                TypeKind::Array => TypeKind::Array,
-                TypeKind::Mapping => TypeKind::Mapping,
-                _ => panic!("Unsupported TypeKind: {:?}", envelope),
+    // Variant names of every enum in the solc `ast` output, keyed by storageLayout type id: t_enum(<Name>)<astId>
+    pub fn enum_variants(blob: &Value) -> HashMap<String, Vec<String>> {
+        let mut variants = HashMap::new();
+        if let Some(sources) = blob["sources"].as_object() {
+            for (_, source) in sources {
+                Self::collect_enum_variants(&source["ast"], &mut variants);
             }
-        } else {
-            let value_type = parsed_type.value_type;
-            if value_type.starts_with("t_struct") {
-                TypeKind::NaiveStruct
-            } else {
-                TypeKind::Primitive
+        }
+        variants
+    }
+    fn collect_enum_variants(node: &Value, variants: &mut HashMap<String, Vec<String>>) {
+        if node["nodeType"] == "EnumDefinition" {
+            if let (Some(name), Some(id)) = (node["name"].as_str(), node["id"].as_u64()) {
+                let members = node["members"].as_array().map(|members| {
+                    members.iter().filter_map(|m| m["name"].as_str().map(|n| n.to_string())).collect()
+                }).unwrap_or_default();
+                variants.insert(format!("t_enum({}){}", name, id), members);
+            }
+        }
+        if let Some(children) = node["nodes"].as_array() {
+            for child in children {
+                Self::collect_enum_variants(child, variants);
             }
         }
     }
-    pub fn parse_type_str(mut type_str: &str) -> ParsedASTType {
-        type_str = type_str.trim_matches(|c| c == '"' || c == '\\');
-        let mut parsed_type = ParsedASTType {
-            envelope: None,
-            key_type: None,
-            value_type: String::new(),
-        };
-
-        // Check if the type is an array
-        if type_str.starts_with("t_array(") {
-            parsed_type.envelope = Some(TypeKind::Array);
 
-            // Extract the element type
-            let element_type = type_str.split("(").nth(1).unwrap().split(")").next().unwrap();
-            parsed_type.value_type = element_type.to_string();
+    // Structs annotated `@custom:storage-location erc7201:<id>`: key=struct name, value=namespace id
+    pub fn erc7201_namespaces(blob: &Value) -> HashMap<String, String> {
+        let mut namespaces = HashMap::new();
+        if let Some(sources) = blob["sources"].as_object() {
+            for (_, source) in sources {
+                Self::collect_erc7201_namespaces(&source["ast"], &mut namespaces);
+            }
         }
-        // Check if the type is a mapping
-        else if type_str.starts_with("t_mapping(") {
-            parsed_type.envelope = Some(TypeKind::Mapping);
-
-            // Extract the key and value types
-            let mut parts = type_str.split(",");
-            let key_type = parts.next().unwrap().split("(").nth(1).unwrap();
-            let value_type = parts.next().unwrap().split(")").next().unwrap();
-            parsed_type.key_type = Some(key_type.to_string());
-            parsed_type.value_type = value_type.to_string();
+        namespaces
+    }
+    fn collect_erc7201_namespaces(node: &Value, namespaces: &mut HashMap<String, String>) {
+        if node["nodeType"] == "StructDefinition" {
+            // Note: a StructuredDocumentation node since solc 0.8.20, a plain string in older ASTs
+            let documentation = node["documentation"]["text"].as_str().or(node["documentation"].as_str()).unwrap_or_default();
+            if let (Some(name), Some(capture)) = (node["name"].as_str(), ERC7201_LOCATION.captures(documentation)) {
+                namespaces.insert(name.to_string(), capture[1].to_string());
+            }
         }
-        // Primitive type
-        else {
-            parsed_type.value_type = type_str.to_string();
+        if let Some(children) = node["nodes"].as_array() {
+            for child in children {
+                Self::collect_erc7201_namespaces(child, namespaces);
+            }
         }
+    }
 
-        parsed_type
+    pub fn type_kind(type_str: &str) -> Result<TypeKind, TypeIdError> {
+        Ok(TypeId::parse(Self::trim_type_str(type_str))?.type_kind())
+    }
+    // Type ids may come straight out of a serialized JSON value
+    fn trim_type_str(type_str: &str) -> &str {
+        type_str.trim_matches(|c| c == '"' || c == '\\')
     }
 }
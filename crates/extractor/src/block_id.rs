@@ -0,0 +1,31 @@
+use serde_json::json;
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockId {
+    Number(u64),
+    Hash(String),
+}
+
+impl BlockId {
+    // Block parameter for eth_call/eth_getStorageAt. Hash pins use the EIP-1898 object form.
+    pub fn to_param(&self) -> Value {
+        match self {
+            BlockId::Number(number) => json!(format!("0x{:x}", number)),
+            BlockId::Hash(hash) => json!({
+                "blockHash": hash,
+                "requireCanonical": true
+            }),
+        }
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockId::Number(number) => write!(f, "{}", number),
+            BlockId::Hash(hash) => write!(f, "{}", hash),
+        }
+    }
+}
@@ -6,44 +6,81 @@
 // use super::perf_config_item::PerfConfigItem;
 // use super::type_kind::TypeKind;
 // use super::eth_call::EthCall;
-// use super::perf_expression_evaluator::PerfExpressionEvaluator;
 // use super::ast_node::ASTNode;
 use super::mc_repo_fetcher::MCRepoFetcher;
+use super::ast_node::ASTNode;
+use super::ast_layout::ASTLayout;
+use super::slot_util::SlotUtil;
+use super::solc_version::SolcResolver;
+use super::error::ExtractorError;
 
 
 use std::collections::HashMap;
 use std::process::Command;
 use serde_json::Value;
-use std::env;
-use std::path::PathBuf;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use regex::Regex;
+use serde_json::{json, Map};
+use ethers::utils::{hex, keccak256};
+
+static STRUCT_STORAGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^t_struct\((\w+)\)\d+_storage$").unwrap());
+
+// One entry of the `errors` array of solc's standard JSON output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolcDiagnostic {
+    pub severity: String, // error, warning or info
+    pub location: Option<String>, // file:line:column, both 1-based
+    pub message: String,
+    pub formatted_message: String, // solc's rendering, with the source excerpt
+}
+
+impl fmt::Display for SolcDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{}: {}: {}", self.severity, location, self.message)?,
+            None => write!(f, "{}: {}", self.severity, self.message)?,
+        }
+        if !self.formatted_message.trim().is_empty() {
+            write!(f, "\n{}", self.formatted_message.trim_end())?;
+        }
+        Ok(())
+    }
+}
 
 pub struct Compiler {
     bundle: String,
     solc_path: String,
     base_path: PathBuf,
     local_repo_path: PathBuf,
+    standard_json_input_path: PathBuf,
+    storage_layout_ast_cache: Option<PathBuf>, // solc outputs by content key; None compiles every time
 }
 
 impl Compiler {
-    pub fn new(solc_path: String, base_path: PathBuf, identifier: String, bundle:String) -> Self {
+    // Same paths as the fetcher.
+    pub fn from_fetcher(solc_path: String, fetcher: &MCRepoFetcher) -> Self {
         Self {
-            bundle: bundle.clone(),
+            bundle: fetcher.bundle.clone(),
             solc_path,
-            base_path: base_path
-                .join(env::var("REPO_PATH").unwrap())
-                .clone(),
-            local_repo_path: base_path
-                .join(env::var("REPO_PATH").unwrap())
-                .join(identifier.clone())
-                .clone(),
+            base_path: fetcher.local_repo_path.clone(),
+            local_repo_path: fetcher.identifier_path.clone(),
+            standard_json_input_path: fetcher.standard_json_input_path.clone(),
+            storage_layout_ast_cache: None,
         }
     }
 
+    pub fn with_cache(mut self, cache_dir: Option<PathBuf>) -> Self {
+        self.storage_layout_ast_cache = cache_dir;
+        self
+    }
+
 
-    pub fn prepare_base_slots(&mut self) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    pub fn prepare_base_slots(&mut self) -> Result<HashMap<String, String>, ExtractorError> {
         let filename = format!("{}/src/{}/storages/BaseSlots.sol", self.local_repo_path.to_string_lossy(), self.bundle);
-        let code = std::fs::read_to_string(filename).unwrap();
+        let code = std::fs::read_to_string(&filename).map_err(|e| ExtractorError::Layout(format!("Failed to read {}: {}", filename, e)))?;
 
         let re = Regex::new(r"baseslot_([A-Z][A-Za-z0-9]+)\s*=\s*(0x[a-fA-F0-9]{64})").unwrap();
 
@@ -59,10 +96,248 @@ impl Compiler {
     }
 
 
-    pub fn prepare_storage_layout(&mut self) -> Result<Value, Box<dyn std::error::Error>> {
-        let standard_json_input_path = self.base_path
-                                                        // .join(env::var("REPO_PATH").unwrap())
-                                                        .join(env::var("STANDARD_JSON_INPUT_LAYOUT_NAME").unwrap());
+    // Schema structs annotated `@custom:storage-location erc7201:<id>`, with the base slot the formula gives.
+    pub fn erc7201_slots(blob: &Value) -> HashMap<String, String> {
+        ASTNode::erc7201_namespaces(blob).into_iter()
+            .map(|(name, namespace_id)| (name, SlotUtil::erc7201_slot(&namespace_id)))
+            .collect()
+    }
+
+    // Annotated slots win; BaseSlots.sol fills in the structs without an annotation.
+    // Returns the merged slots and a warning per struct where the two sources disagree.
+    pub fn reconcile_base_slots(erc7201: HashMap<String, String>, declared: HashMap<String, String>) -> (HashMap<String, String>, Vec<String>) {
+        let mut warnings = Vec::new();
+        let mut base_slots = erc7201;
+        let mut names: Vec<&String> = declared.keys().collect();
+        names.sort();
+        for name in names {
+            let slot = &declared[name];
+            match base_slots.get(name) {
+                Some(computed) if computed.to_lowercase() != slot.to_lowercase() => {
+                    warnings.push(format!("BaseSlots.sol declares {} for {}, but its erc7201 annotation gives {}; using the latter", slot, name, computed));
+                },
+                Some(_) => {},
+                None => {
+                    base_slots.insert(name.clone(), slot.clone());
+                },
+            }
+        }
+        (base_slots, warnings)
+    }
+
+    // forge build output reshaped like solc's standard JSON output, so the rest of the pipeline can't tell the difference:
+    // {"sources": {<unit>: {"ast": ...}}, "contracts": {<unit>: {<contract>: {"storageLayout": ...}}}}
+    // `path` is an out/ dir (artifacts plus out/build-info/*.json when there) or a single build-info file.
+    pub fn load_artifacts(path: &Path) -> Result<Value, ExtractorError> {
+        let mut output = json!({ "sources": {}, "contracts": {} });
+        if path.is_file() {
+            Self::merge_build_info(&mut output, &Self::read_json(path)?, path)?;
+            return Ok(output);
+        }
+        let entries = fs::read_dir(path).map_err(|e| ExtractorError::Compile(format!("No forge artifacts at {} ({}); run `forge build` first", path.display(), e)))?;
+        let mut dirs: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.is_dir()).collect();
+        dirs.sort();
+        for dir in dirs {
+            let build_info = dir.file_name().map(|name| name == "build-info").unwrap_or(false);
+            let mut files: Vec<PathBuf> = fs::read_dir(&dir).map_err(|e| ExtractorError::Compile(format!("Failed to read {}: {}", dir.display(), e)))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map(|extension| extension == "json").unwrap_or(false))
+                .collect();
+            files.sort();
+            for file in files {
+                let content = Self::read_json(&file)?;
+                if build_info {
+                    Self::merge_build_info(&mut output, &content, &file)?;
+                } else {
+                    Self::merge_artifact(&mut output, &content, &file);
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    fn merge_build_info(output: &mut Value, build_info: &Value, path: &Path) -> Result<(), ExtractorError> {
+        let solc_output = build_info["output"].as_object().ok_or(ExtractorError::Compile(format!("{} is not a build-info file (no output)", path.display())))?;
+        for key in ["sources", "contracts"] {
+            if let Some(entries) = solc_output.get(key).and_then(|entries| entries.as_object()) {
+                for (unit, entry) in entries {
+                    output[key][unit] = entry.clone();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // out/<File>.sol/<Contract>.json: the unit and contract come from metadata's compilationTarget, else the ast and file name
+    fn merge_artifact(output: &mut Value, artifact: &Value, path: &Path) {
+        let target = artifact["metadata"]["settings"]["compilationTarget"].as_object().and_then(|target| target.iter().next());
+        let (unit, contract) = match (target, artifact["ast"]["absolutePath"].as_str()) {
+            (Some((unit, contract)), _) => (unit.clone(), contract.as_str().unwrap_or_default().to_string()),
+            (None, Some(unit)) => (unit.to_string(), path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()),
+            (None, None) => return,
+        };
+        if artifact["ast"].is_object() {
+            output["sources"][&unit] = json!({ "ast": artifact["ast"] });
+        }
+        if artifact["storageLayout"].is_object() {
+            if !output["contracts"][&unit].is_object() {
+                output["contracts"][&unit] = json!({});
+            }
+            output["contracts"][&unit][&contract] = json!({ "storageLayout": artifact["storageLayout"] });
+        }
+    }
+
+    // The storageLayout types of the bundle's contracts (units under src/<bundle>/), when they cover every base slot struct.
+    // Note: a struct only shows up there when some contract of the bundle has it as a state variable
+    pub fn artifact_layout(blob: &Value, bundle: &str, base_slots: &HashMap<String, String>) -> Result<Option<Value>, ExtractorError> {
+        let mut types = Map::new();
+        let prefix = format!("src/{}/", bundle);
+        if let Some(contracts) = blob["contracts"].as_object() {
+            let mut units: Vec<&String> = contracts.keys().filter(|unit| unit.starts_with(&prefix)).collect();
+            units.sort();
+            for unit in units {
+                for (_, contract) in contracts[unit].as_object().into_iter().flatten() {
+                    if let Some(contract_types) = contract["storageLayout"]["types"].as_object() {
+                        types.extend(contract_types.clone());
+                    }
+                }
+            }
+        }
+
+        // Note: solc's entry of a UDVT only has its label; the decoder needs the underlying type
+        for (type_id, (underlying_id, underlying)) in ASTLayout::udvt_underlying(blob) {
+            if let Some(entry) = types.get_mut(&type_id) {
+                entry["underlying"] = json!(underlying_id);
+                types.entry(underlying_id).or_insert(underlying);
+            }
+        }
+
+        let mut names: Vec<&String> = base_slots.keys().collect();
+        names.sort();
+        let mut storage = Vec::new();
+        for name in names {
+            let type_id = types.keys().find(|type_id| STRUCT_STORAGE.captures(type_id).map(|capture| &capture[1] == name.as_str()).unwrap_or(false));
+            match type_id {
+                Some(type_id) => {
+                    let slot = SlotUtil::slot_to_decimal(&base_slots[name]).map_err(|e| ExtractorError::Layout(format!("Base slot {} of {}: {}", base_slots[name], name, e)))?;
+                    storage.push(json!({ "label": name, "offset": 0, "slot": slot, "type": type_id }));
+                },
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(json!({ "storage": storage, "types": types })))
+    }
+
+    fn read_json(path: &Path) -> Result<Value, ExtractorError> {
+        let content = fs::read_to_string(path).map_err(|e| ExtractorError::Compile(format!("Failed to read {}: {}", path.display(), e)))?;
+        serde_json::from_str(&content).map_err(|e| ExtractorError::Compile(format!("{} is not JSON: {}", path.display(), e)))
+    }
+
+    // solc's standard JSON output for the input gen_standard_json_input wrote: the ast of every source.
+    // Note: solc resolves the imports itself, so which sources went in is only known from an output.
+    // <cache>/<input key>.sources.json remembers them; the output is stored under a key over the input and their contents.
+    pub fn prepare_storage_layout(&mut self) -> Result<Value, ExtractorError> {
+        let cache_dir = match &self.storage_layout_ast_cache {
+            Some(cache_dir) => cache_dir.clone(),
+            None => {
+                let output = self.run_solc()?;
+                self.report_diagnostics(&output)?;
+                return Ok(output);
+            },
+        };
+        let input = fs::read(&self.standard_json_input_path).map_err(|e| ExtractorError::Compile(format!("Failed to read {}: {}", self.standard_json_input_path.display(), e)))?;
+        let mut preimage = SolcResolver::binary_version(&self.solc_path)?.to_string().into_bytes();
+        preimage.extend_from_slice(&input);
+        let input_key = hex::encode(keccak256(&preimage));
+        let manifest_path = cache_dir.join(format!("{}.sources.json", input_key));
+
+        let units: Option<Vec<String>> = fs::read_to_string(&manifest_path).ok().and_then(|content| serde_json::from_str(&content).ok());
+        if let Some(output_path) = units.and_then(|units| self.content_key(&input_key, &units)).map(|key| cache_dir.join(format!("{}.json", key))) {
+            if let Ok(output) = Self::read_json(&output_path) {
+                eprintln!("Using cached solc output {}", output_path.display());
+                self.report_diagnostics(&output)?;
+                return Ok(output);
+            }
+        }
+
+        let output = self.run_solc()?;
+        // Note: failed compiles never reach the cache
+        self.report_diagnostics(&output)?;
+        let mut units: Vec<String> = output["sources"].as_object().map(|sources| sources.keys().cloned().collect()).unwrap_or_default();
+        units.sort();
+        if let Some(key) = self.content_key(&input_key, &units) {
+            // Note: a cache that can't be written only costs the next run a compile
+            let written = fs::create_dir_all(&cache_dir)
+                .and_then(|_| fs::write(cache_dir.join(format!("{}.json", key)), output.to_string()))
+                .and_then(|_| fs::write(&manifest_path, json!(units).to_string()));
+            if let Err(err) = written {
+                eprintln!("Warning: failed to cache the solc output in {}: {}", cache_dir.display(), err);
+            }
+        }
+        Ok(output)
+    }
+
+    // Prints warnings (and infos) to stderr; errors fail the compile, all of them in the message.
+    pub fn report_diagnostics(&self, output: &Value) -> Result<(), ExtractorError> {
+        let diagnostics = self.diagnostics(output);
+        let errors: Vec<String> = diagnostics.iter().filter(|diagnostic| diagnostic.severity == "error").map(|diagnostic| diagnostic.to_string()).collect();
+        for diagnostic in diagnostics.iter().filter(|diagnostic| diagnostic.severity != "error") {
+            eprintln!("{}", diagnostic);
+        }
+        if !errors.is_empty() {
+            return Err(ExtractorError::Compile(format!("solc reported {} error(s):\n{}", errors.len(), errors.join("\n"))));
+        }
+        Ok(())
+    }
+
+    pub fn diagnostics(&self, output: &Value) -> Vec<SolcDiagnostic> {
+        let mut diagnostics = Vec::new();
+        for error in output["errors"].as_array().into_iter().flatten() {
+            let message = error["message"].as_str().unwrap_or_default().to_string();
+            diagnostics.push(SolcDiagnostic {
+                severity: error["severity"].as_str().unwrap_or("error").to_string(),
+                location: self.location(&error["sourceLocation"]),
+                formatted_message: error["formattedMessage"].as_str().unwrap_or(&message).to_string(),
+                message: match error["type"].as_str() {
+                    Some(error_type) => format!("{}: {}", error_type, message),
+                    None => message,
+                },
+            });
+        }
+        diagnostics
+    }
+
+    // sourceLocation {"file", "start", "end"} counts bytes; line and column come from the source on disk.
+    fn location(&self, source_location: &Value) -> Option<String> {
+        let file = source_location["file"].as_str()?;
+        let start = match source_location["start"].as_u64() {
+            Some(start) => start as usize,
+            None => return Some(file.to_string()),
+        };
+        match fs::read_to_string(self.local_repo_path.join(file)) {
+            Ok(content) if start <= content.len() && content.is_char_boundary(start) => {
+                let before = &content[..start];
+                let line = before.matches('\n').count() + 1;
+                let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+                Some(format!("{}:{}:{}", file, line, column))
+            },
+            _ => Some(file.to_string()),
+        }
+    }
+
+    // keccak256 over the input key and every source unit's name and content; None when one can't be read.
+    fn content_key(&self, input_key: &str, units: &[String]) -> Option<String> {
+        let mut preimage = input_key.as_bytes().to_vec();
+        for unit in units {
+            let content = fs::read(self.local_repo_path.join(unit)).ok()?;
+            preimage.extend_from_slice(unit.as_bytes());
+            preimage.extend_from_slice(&keccak256(&content));
+        }
+        Some(hex::encode(keccak256(&preimage)))
+    }
+
+    fn run_solc(&self) -> Result<Value, ExtractorError> {
+        let standard_json_input_path = self.standard_json_input_path.clone();
 
 
         match Command::new(&self.solc_path)
@@ -75,9 +350,9 @@ impl Compiler {
             // .arg("--devdoc")
             .output() {
                 Ok(output)=>{
-                    let stdout = String::from_utf8(output.stdout)?;
-                    if stdout.len() == 0 {
-                        panic!("solc compilation for storage layout generated null result.");
+                    let stdout = String::from_utf8(output.stdout).map_err(|e| ExtractorError::Compile(format!("solc output is not UTF-8: {}", e)))?;
+                    if stdout.is_empty() {
+                        return Err(ExtractorError::Compile("solc compilation for storage layout generated null result.".to_string()));
                     }
 
                     match serde_json::from_str(&stdout) {
@@ -85,12 +360,12 @@ impl Compiler {
                             Ok(parsed)            
                         },
                         Err(err)=>{
-                            panic!("{}", err);
+                            Err(ExtractorError::Compile(format!("Failed to parse solc output: {}", err)))
                         }
                     }
                 },
                 Err(err)=>{
-                    panic!("{}", err);
+                    Err(ExtractorError::Compile(format!("Failed to run {}: {}", self.solc_path, err)))
                 }
             }
     }
@@ -102,27 +377,26 @@ impl Compiler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support;
+    use std::env;
     use tempfile::tempdir;
     use std::fs;
 
     #[test]
+    #[ignore = "needs REPO_IDENTIFIER/BUNDLE_NAME in .env and network access"]
     fn test_prepare_base_slots() {
         dotenv::dotenv().ok();
 
         let tempdir = tempdir().unwrap();
         let pathbuf_temppath = tempdir.into_path();
 
-        let fetcher = MCRepoFetcher::new(env::var("REPO_IDENTIFIER").unwrap(), env::var("BUNDLE_NAME").unwrap(), Some(pathbuf_temppath.clone()));
+        let fetcher = MCRepoFetcher::with_repo_path(env::var("REPO_IDENTIFIER").unwrap(), env::var("BUNDLE_NAME").unwrap(), pathbuf_temppath.clone(), ".repo").unwrap();
 
         if !fetcher.local_repo_path.exists() {
             std::fs::create_dir_all(fetcher.local_repo_path.clone()).unwrap();
         }
-        if !fetcher.standard_json_input_layout_path.exists() {
-            let copy_source = env::current_dir().unwrap().join(PathBuf::from(env::var("REPO_PATH").unwrap()).join(env::var("STANDARD_JSON_INPUT_LAYOUT_SAMPLE_NAME").unwrap()));
-            fs::copy(copy_source.clone(), &fetcher.standard_json_input_layout_path).unwrap();    
-        }
 
-        let mut compiler = Compiler::new("solc".to_string(), fetcher.base_path.clone(), fetcher.identifier.clone(), fetcher.bundle.clone());
+        let mut compiler = Compiler::from_fetcher("solc".to_string(), &fetcher);
         let baseslots = match compiler.prepare_base_slots() {
             Ok(blob) => blob,
             Err(err) => {
@@ -131,33 +405,156 @@ mod tests {
         };
 
 
-        assert!(baseslots.len() > 0);
+        assert!(!baseslots.is_empty());
 
     }
 
 
     #[test]
+    fn test_reconcile_base_slots() {
+        let ast = serde_json::json!({ "sources": { "src/textDAO/storages/Schema.sol": { "ast": {
+            "nodeType": "SourceUnit",
+            "nodes": [{ "nodeType": "ContractDefinition", "name": "Schema", "nodes": [
+                { "nodeType": "StructDefinition", "name": "Main", "documentation": { "nodeType": "StructuredDocumentation", "text": " @custom:storage-location erc7201:example.main" } },
+                { "nodeType": "StructDefinition", "name": "Member", "documentation": "@custom:storage-location erc7201:textDAO.Member" },
+                { "nodeType": "StructDefinition", "name": "Plain" }
+            ]}]
+        }}}});
+        let namespaces = ASTNode::erc7201_namespaces(&ast);
+        assert_eq!(namespaces, HashMap::from([
+            ("Main".to_string(), "example.main".to_string()),
+            ("Member".to_string(), "textDAO.Member".to_string()),
+        ]));
+
+        let erc7201 = Compiler::erc7201_slots(&ast);
+        let declared = HashMap::from([
+            ("Main".to_string(), "0x183A6125C38840424C4A85FA12BAB2AB606C4B6D0E7CC73C0C06BA5300EAB500".to_string()),
+            ("Member".to_string(), format!("0x{}", "11".repeat(32))),
+            ("Legacy".to_string(), format!("0x{}", "22".repeat(32))),
+        ]);
+        let (base_slots, warnings) = Compiler::reconcile_base_slots(erc7201.clone(), declared);
+        assert_eq!(base_slots.len(), 3);
+        assert_eq!(base_slots["Member"], erc7201["Member"]);
+        assert_eq!(base_slots["Legacy"], format!("0x{}", "22".repeat(32)));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with(&format!("BaseSlots.sol declares 0x{} for Member", "11".repeat(32))));
+    }
+
+    #[test]
+    fn test_load_artifacts() {
+        let out = tempdir().unwrap().into_path();
+        let foo = json!({ "encoding": "inplace", "label": "struct Schema.Foo", "numberOfBytes": "32", "members": [
+            { "astId": 3, "contract": "src/textDAO/storages/Storage.sol:Storage", "label": "count", "offset": 0, "slot": "0", "type": "t_uint256" }
+        ]});
+        let uint256 = test_support::elementary()["t_uint256"].clone();
+        fs::create_dir_all(out.join("Storage.sol")).unwrap();
+        fs::write(out.join("Storage.sol/Storage.json"), json!({
+            "metadata": { "settings": { "compilationTarget": { "src/textDAO/storages/Storage.sol": "Storage" } } },
+            "ast": { "absolutePath": "src/textDAO/storages/Storage.sol", "nodeType": "SourceUnit", "nodes": [] },
+            "storageLayout": { "storage": [], "types": { "t_struct(Foo)2_storage": foo.clone(), "t_uint256": uint256 } }
+        }).to_string()).unwrap();
+        fs::create_dir_all(out.join("Other.sol")).unwrap();
+        fs::write(out.join("Other.sol/Other.json"), json!({
+            "ast": { "absolutePath": "src/otherDAO/Other.sol", "nodeType": "SourceUnit", "nodes": [] },
+            "storageLayout": { "storage": [], "types": { "t_struct(Bar)7_storage": foo } }
+        }).to_string()).unwrap();
+        fs::create_dir_all(out.join("build-info")).unwrap();
+        fs::write(out.join("build-info/0a1b.json"), json!({
+            "solcVersion": "0.8.24",
+            "output": { "sources": { "src/textDAO/storages/Schema.sol": { "ast": { "nodeType": "SourceUnit", "nodes": [] } } } }
+        }).to_string()).unwrap();
+
+        let blob = Compiler::load_artifacts(&out).unwrap();
+        let mut units: Vec<&String> = blob["sources"].as_object().unwrap().keys().collect();
+        units.sort();
+        assert_eq!(units, vec!["src/otherDAO/Other.sol", "src/textDAO/storages/Schema.sol", "src/textDAO/storages/Storage.sol"]);
+        assert!(blob["contracts"]["src/otherDAO/Other.sol"]["Other"]["storageLayout"].is_object());
+
+        let base_slots = HashMap::from([("Foo".to_string(), "0x100".to_string())]);
+        let layout = Compiler::artifact_layout(&blob, "textDAO", &base_slots).unwrap().unwrap();
+        assert_eq!(layout["storage"], json!([{ "label": "Foo", "offset": 0, "slot": "256", "type": "t_struct(Foo)2_storage" }]));
+        assert_eq!(layout["types"].as_object().unwrap().len(), 2);
+        // Bar belongs to another bundle
+        let base_slots = HashMap::from([("Bar".to_string(), "0x100".to_string())]);
+        assert!(Compiler::artifact_layout(&blob, "textDAO", &base_slots).unwrap().is_none());
+
+        assert!(Compiler::load_artifacts(&out.join("build-info/0a1b.json")).unwrap()["sources"]["src/textDAO/storages/Schema.sol"].is_object());
+        assert!(Compiler::load_artifacts(&out.join("missing")).unwrap_err().to_string().contains("run `forge build` first"));
+    }
+
+    #[test]
+    fn test_prepare_storage_layout_cache() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let bin = tempdir().unwrap().into_path();
+        let solc = bin.join("solc");
+        fs::write(&solc, format!(
+            "#!/bin/sh\nif [ \"$1\" = \"--version\" ]; then echo 'Version: 0.8.24+commit.e11b9ed9'; exit 0; fi\necho run >> {}\necho '{}'\n",
+            bin.join("runs").display(),
+            json!({ "sources": { "src/textDAO/storages/Schema.sol": { "id": 0, "ast": {} } } })
+        )).unwrap();
+        fs::set_permissions(&solc, fs::Permissions::from_mode(0o755)).unwrap();
+        let runs = || fs::read_to_string(bin.join("runs")).unwrap().lines().count();
+
+        let (checkout, fetcher) = test_support::checkout("// v1");
+        fetcher.gen_standard_json_input().unwrap();
+        let cache = tempdir().unwrap().into_path();
+        let mut compiler = Compiler::from_fetcher(solc.display().to_string(), &fetcher).with_cache(Some(cache.clone()));
+
+        let output = compiler.prepare_storage_layout().unwrap();
+        assert_eq!(compiler.prepare_storage_layout().unwrap(), output);
+        assert_eq!(runs(), 1);
+
+        // a changed source misses, and the old entry stays for when it changes back
+        fs::write(checkout.join("src/textDAO/storages/Schema.sol"), "// v2").unwrap();
+        compiler.prepare_storage_layout().unwrap();
+        assert_eq!(runs(), 2);
+        fs::write(checkout.join("src/textDAO/storages/Schema.sol"), "// v1").unwrap();
+        compiler.prepare_storage_layout().unwrap();
+        assert_eq!(runs(), 2);
+        assert_eq!(fs::read_dir(&cache).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_report_diagnostics() {
+        let (_, fetcher) = test_support::checkout("pragma solidity ^0.8.24;\n\ninterface Schema {\n    struct Foo { uint256 x }\n}\n");
+        let compiler = Compiler::from_fetcher("solc".to_string(), &fetcher);
+
+        let warning = json!({
+            "component": "general", "severity": "warning", "type": "Warning", "message": "Unreachable code.",
+            "formattedMessage": "Warning: Unreachable code.\n", "sourceLocation": { "file": "src/textDAO/storages/Schema.sol", "start": 0, "end": 6 }
+        });
+        let error = json!({
+            "component": "general", "severity": "error", "type": "ParserError", "message": "Expected ';' but got '}'",
+            "formattedMessage": "ParserError: Expected ';' but got '}'\n --> src/textDAO/storages/Schema.sol:4:28:\n", "sourceLocation": { "file": "src/textDAO/storages/Schema.sol", "start": 72, "end": 73 }
+        });
+
+        let diagnostics = compiler.diagnostics(&json!({ "errors": [warning.clone(), error.clone()] }));
+        assert_eq!(diagnostics[0].location, Some("src/textDAO/storages/Schema.sol:1:1".to_string()));
+        assert_eq!(diagnostics[1].to_string().lines().next().unwrap(), "error: src/textDAO/storages/Schema.sol:4:28: ParserError: Expected ';' but got '}'");
+
+        assert!(compiler.report_diagnostics(&json!({ "errors": [warning.clone()], "sources": {} })).is_ok());
+        let message = compiler.report_diagnostics(&json!({ "errors": [warning, error] })).unwrap_err().to_string();
+        assert!(message.contains("solc reported 1 error(s):\nerror: src/textDAO/storages/Schema.sol:4:28: ParserError"));
+    }
+
+    #[test]
+    #[ignore = "needs REPO_IDENTIFIER/BUNDLE_NAME in .env and network access"]
     fn test_prepare_storage_layout() {
         dotenv::dotenv().ok();
 
         let tempdir = tempdir().unwrap();
         let pathbuf_temppath = tempdir.into_path();
 
-        let fetcher = MCRepoFetcher::new(env::var("REPO_IDENTIFIER").unwrap(), env::var("BUNDLE_NAME").unwrap(), Some(pathbuf_temppath.clone()));
+        let fetcher = MCRepoFetcher::with_repo_path(env::var("REPO_IDENTIFIER").unwrap(), env::var("BUNDLE_NAME").unwrap(), pathbuf_temppath.clone(), ".repo").unwrap();
 
         if !fetcher.local_repo_path.exists() {
             std::fs::create_dir_all(fetcher.local_repo_path.clone()).unwrap();
         }
-        if !fetcher.standard_json_input_layout_path.exists() {
-            let copy_source = env::current_dir().unwrap().join(PathBuf::from(env::var("REPO_PATH").unwrap()).join(env::var("STANDARD_JSON_INPUT_LAYOUT_SAMPLE_NAME").unwrap()));
-            fs::copy(copy_source.clone(), &fetcher.standard_json_input_layout_path).unwrap();    
-        }
     
-        let mut compiler = Compiler::new("solc".to_string(), fetcher.base_path.clone(), fetcher.identifier.clone(), fetcher.bundle.clone());
+        let mut compiler = Compiler::from_fetcher("solc".to_string(), &fetcher);
         let base_slots = compiler.prepare_base_slots().unwrap();
     
-        let base_slots_vec: Vec<String> = base_slots.keys().cloned().collect();
-        fetcher.gen_dummy_contract(&base_slots_vec).unwrap();
         fetcher.gen_standard_json_input().unwrap();   
 
         let storage_layout_blob = match compiler.prepare_storage_layout() {
@@ -167,12 +564,8 @@ mod tests {
             }
         };
 
-        let types = storage_layout_blob
-                                .get("contracts").unwrap()
-                                .get(&format!("src/{}/storages/Dummy.sol", fetcher.bundle.clone())).unwrap()
-                                .get("Dummy").unwrap()
-                                .get("storageLayout").unwrap()
-                                .get("types").unwrap();
+        let storage_layout = ASTLayout::storage_layout(&storage_layout_blob, &base_slots).unwrap();
+        let types = storage_layout.get("types").unwrap();
 
         assert!(types.is_object());
     }
@@ -0,0 +1,216 @@
+use super::mc_repo_fetcher::MCRepoFetcher;
+use super::storage_layout::StorageLayout;
+use super::config_util::ConfigUtil;
+use super::plan::Planner;
+use super::type_id::TypeId;
+use super::type_kind::TypeKind;
+use super::error::ExtractorError;
+
+use std::collections::HashMap;
+use std::fmt;
+use yaml_rust::Yaml;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LintLevel {
+    Error,   // the scan would skip or misread something
+    Warning, // harmless but probably not what was meant
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LintIssue {
+    pub level: LintLevel,
+    pub path: String, // constraint key as written in Indexer.yaml, or a schema path
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            LintLevel::Error => "error",
+            LintLevel::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", level, self.path, self.message)
+    }
+}
+
+// key=cid, value=(schema path, type kind, key type of a mapping)
+type SchemaNodes = HashMap<usize, (String, TypeKind, Option<String>)>;
+
+// Static checks of Indexer.yaml, against the nodes of the layout a scan reaches within `max_steps`, when one is given.
+pub struct ConfigLint;
+
+impl ConfigLint {
+    pub fn lint(fetcher: &MCRepoFetcher, layout: Option<&StorageLayout>, max_steps: usize) -> Result<Vec<LintIssue>, ExtractorError> {
+        let mut issues = Vec::new();
+        let doc = match fetcher.docs.first() {
+            Some(doc) => doc,
+            None => {
+                issues.push(Self::error(&fetcher.perf_config_path.display().to_string(), "Indexer.yaml is missing or empty"));
+                return Ok(issues);
+            },
+        };
+        let constraints = match doc["constraints"].as_hash() {
+            Some(constraints) => constraints,
+            None => {
+                issues.push(Self::error("constraints", "constraints must be a map of schema paths"));
+                return Ok(issues);
+            },
+        };
+
+        let nodes: Option<SchemaNodes> = match layout {
+            Some(layout) => Some(Self::schema_nodes(layout, max_steps)?),
+            None => None,
+        };
+
+        let mut constrained: Vec<usize> = Vec::new();
+        for (key, value) in constraints {
+            let key = match key {
+                Yaml::String(key) => key,
+                _ => {
+                    issues.push(Self::error(&format!("{:?}", key), "constraint keys must be strings"));
+                    continue;
+                },
+            };
+            Self::lint_fields(key, value, &mut issues);
+
+            let cid = ConfigUtil::calc_id(ConfigUtil::to_class_paths(fetcher.resolve_user_defined_vars(key.clone())));
+            constrained.push(cid);
+            if let Some(nodes) = &nodes {
+                match nodes.get(&cid) {
+                    None => issues.push(Self::error(key, "doesn't match any node of the schema")),
+                    Some((_, TypeKind::Mapping, key_type)) => {
+                        if let Some(key_type) = key_type.as_deref().filter(|key_type| !Self::is_enumerable(key_type)) {
+                            issues.push(Self::error(key, &format!("{} keys can't be enumerated from a from/to range, the scan will skip this mapping", key_type)));
+                        }
+                    },
+                    Some((_, TypeKind::Array, _)) | Some((_, TypeKind::FixedArray(_), _)) => {
+                        issues.push(Self::warning(key, "arrays take their length from storage, this constraint is unused"));
+                    },
+                    Some(_) => issues.push(Self::error(key, "constraints only apply to mappings")),
+                }
+            }
+        }
+
+        // Mappings without a constraint are skipped by the scan
+        if let Some(nodes) = &nodes {
+            let mut unconstrained: Vec<&String> = nodes.iter()
+                .filter(|(cid, (_, kind, _))| *kind == TypeKind::Mapping && !constrained.contains(cid))
+                .map(|(_, (path, _, _))| path)
+                .collect();
+            unconstrained.sort();
+            for path in unconstrained {
+                issues.push(Self::error(path, "mapping has no constraint and will be skipped"));
+            }
+        }
+        Ok(issues)
+    }
+
+    fn lint_fields(key: &str, value: &Yaml, issues: &mut Vec<LintIssue>) {
+        let fields = match value.as_hash() {
+            Some(fields) => fields,
+            None => {
+                issues.push(Self::error(key, "expected a map of <expression>: from|to"));
+                return;
+            },
+        };
+        let mut seen: Vec<&str> = Vec::new();
+        for (expression, field) in fields {
+            match (expression.as_str(), field.as_str()) {
+                (Some(_), Some(field)) if field == "from" || field == "to" => {
+                    if seen.contains(&field) {
+                        issues.push(Self::error(key, &format!("{} is given twice", field)));
+                    }
+                    seen.push(field);
+                },
+                _ => issues.push(Self::error(key, &format!("unknown field {:?}: {:?}", expression, field))),
+            }
+        }
+        for field in ["from", "to"] {
+            if !seen.contains(&field) {
+                issues.push(Self::error(key, &format!("{} is missing", field)));
+            }
+        }
+    }
+
+    // Class paths of every planned node, the way Executable::cid() sees them.
+    fn schema_nodes(layout: &StorageLayout, max_steps: usize) -> Result<SchemaNodes, ExtractorError> {
+        let mut nodes = HashMap::new();
+        for entry in Planner::plan(layout, max_steps)? {
+            let class_path = entry.path.replace("[key]", "").replace("[i]", "");
+            let cid = ConfigUtil::calc_id(class_path.split('.').map(|part| part.to_string()).collect());
+            let type_id = TypeId::parse(&entry.type_id)?;
+            let key_type = match &type_id {
+                TypeId::Mapping { key, .. } => Some(key.to_string()),
+                _ => None,
+            };
+            // Note: an element shares its container's class path, the container comes first
+            nodes.entry(cid).or_insert((entry.path.replace("[key]", "[i]"), type_id.type_kind(), key_type));
+        }
+        Ok(nodes)
+    }
+
+    // Note: the scan feeds a mapping the decimal keys from..to; an address can't be spelled that way (see SlotUtil::encode_mapping_key)
+    fn is_enumerable(key_type: &str) -> bool {
+        !(key_type.starts_with("t_address") || key_type.starts_with("t_contract("))
+    }
+
+    fn error(path: &str, message: &str) -> LintIssue {
+        LintIssue { level: LintLevel::Error, path: path.to_string(), message: message.to_string() }
+    }
+
+    fn warning(path: &str, message: &str) -> LintIssue {
+        LintIssue { level: LintLevel::Warning, path: path.to_string(), message: message.to_string() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+    use yaml_rust::YamlLoader;
+
+    #[test]
+    fn test_lint_against_layout() {
+        let types = test_support::types(json!({
+            "t_struct(Foo)1_storage": {
+                "encoding": "inplace", "label": "struct Schema.Foo", "numberOfBytes": "128",
+                "members": [
+                    { "astId": 2, "label": "votes", "offset": 0, "slot": "0", "type": "t_mapping(t_uint256,t_uint256)" },
+                    { "astId": 3, "label": "voters", "offset": 0, "slot": "1", "type": "t_mapping(t_uint256,t_address)" },
+                    { "astId": 4, "label": "items", "offset": 0, "slot": "2", "type": "t_array(t_uint256)dyn_storage" },
+                    { "astId": 5, "label": "balances", "offset": 0, "slot": "3", "type": "t_mapping(t_address,t_uint256)" }
+                ]
+            },
+            "t_mapping(t_uint256,t_uint256)": { "encoding": "mapping", "label": "mapping(uint256 => uint256)", "numberOfBytes": "32", "key": "t_uint256", "value": "t_uint256" },
+            "t_mapping(t_uint256,t_address)": { "encoding": "mapping", "label": "mapping(uint256 => address)", "numberOfBytes": "32", "key": "t_uint256", "value": "t_address" },
+            "t_mapping(t_address,t_uint256)": { "encoding": "mapping", "label": "mapping(address => uint256)", "numberOfBytes": "32", "key": "t_address", "value": "t_uint256" },
+            "t_array(t_uint256)dyn_storage": { "encoding": "dynamic_array", "label": "uint256[]", "numberOfBytes": "32", "base": "t_uint256" }
+        }));
+        let layout = test_support::layout(types, &[("Foo", "0x100")]);
+        let (_, mut fetcher) = test_support::checkout("");
+        fetcher.docs = YamlLoader::load_from_str("
+constraints:
+  Foo.votes:
+    \"0\": from
+    \"10\": to
+  Foo.items:
+    \"0\": from
+    \"1\": to
+  Foo.missing:
+    \"0\": from
+  Foo.balances:
+    \"0\": from
+    \"10\": to
+").unwrap();
+
+        let issues: Vec<String> = ConfigLint::lint(&fetcher, Some(&layout), 16).unwrap().iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(issues, vec![
+            "warning: Foo.items: arrays take their length from storage, this constraint is unused",
+            "error: Foo.missing: to is missing",
+            "error: Foo.missing: doesn't match any node of the schema",
+            "error: Foo.balances: t_address keys can't be enumerated from a from/to range, the scan will skip this mapping",
+            "error: Foo.voters: mapping has no constraint and will be skipped",
+        ]);
+    }
+}
@@ -1,10 +1,11 @@
-use super::executor::Executor;
 use super::registry::Registry;
-use super::ast_node::ASTNode;
-use std::collections::HashMap;
-use serde_json::Value;
+use super::slot_reader::SlotReader;
+use super::indexer_config::IndexerConfig;
+use std::rc::Rc;
 
 #[derive(Clone)]
 pub struct Context {
     pub registry: Registry,
+    pub reader: Rc<dyn SlotReader>,
+    pub config: IndexerConfig,
 }
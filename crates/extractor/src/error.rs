@@ -0,0 +1,43 @@
+use super::type_id::TypeIdError;
+
+use std::error::Error;
+use std::fmt;
+
+// Crate-wide failure kinds. Messages are plain strings so the registry can keep
+// (and clone) the errors of skipped subtrees next to the values it did read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExtractorError {
+    Rpc(String),     // JSON-RPC transport or node-side failures
+    Fetch(String),   // cloning and preparing the guest protocol repo
+    Compile(String), // solc invocation and its standard JSON input/output
+    Layout(String),  // storageLayout types table and type identifiers
+    Config(String),  // env vars and Indexer.yaml constraints
+    Slot(String),    // absolute slot derivation and slot-word decoding
+}
+
+impl fmt::Display for ExtractorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractorError::Rpc(message) => write!(f, "rpc error: {}", message),
+            ExtractorError::Fetch(message) => write!(f, "fetch error: {}", message),
+            ExtractorError::Compile(message) => write!(f, "compile error: {}", message),
+            ExtractorError::Layout(message) => write!(f, "layout error: {}", message),
+            ExtractorError::Config(message) => write!(f, "config error: {}", message),
+            ExtractorError::Slot(message) => write!(f, "slot error: {}", message),
+        }
+    }
+}
+
+impl Error for ExtractorError {}
+
+impl From<TypeIdError> for ExtractorError {
+    fn from(err: TypeIdError) -> Self {
+        ExtractorError::Layout(err.to_string())
+    }
+}
+
+impl From<reqwest::Error> for ExtractorError {
+    fn from(err: reqwest::Error) -> Self {
+        ExtractorError::Rpc(err.to_string())
+    }
+}
@@ -1,25 +1,30 @@
+use super::provider::Provider;
+use super::block_id::BlockId;
+use super::error::ExtractorError;
+
 use std::collections::HashMap;
-use std::fs;
-use std::path::Path;
 use serde_json::json;
 use serde_json::Value;
 use reqwest::Client;
 
+const STORAGE_AT_BATCH_SIZE: usize = 100; // public RPCs commonly cap batch length
+const CHAIN_IDS: &str = include_str!("../chainIds.json"); // key=chain id, value=network name
 
 pub struct EthCall;
 
 impl EthCall {
-    pub async fn get_values_by_slots(slots: &HashMap<usize, String>, network: &str, contract_address: &str, contract_code: &str) -> Result<HashMap<usize, String>, Box<dyn std::error::Error>> {
-        let chain_list = Self::get_chain_list();
-        let api_url = "http://127.0.0.1:8545";
-
+    pub async fn get_values_by_slots(slots: &HashMap<usize, String>, provider: &Provider, block: &BlockId) -> Result<HashMap<usize, String>, ExtractorError> {
         let mut data = String::new();
-        for (_, slot) in slots {
-            data.push_str(&slot);
+        for slot in slots.values() {
+            data.push_str(slot);
         }
 
+        let contract_code = match &provider.contract_code {
+            Some(code) => code,
+            None => return Err(ExtractorError::Config("CONTRACT_CODE is required by the override reader.".to_string())),
+        };
         let overrides = json!({
-            contract_address: {
+            provider.contract_addr.clone(): {
                 "code": contract_code
             }
         });
@@ -31,40 +36,34 @@ impl EthCall {
             "method": "eth_call",
             "params": [
                 {
-                    "to": contract_address,
+                    "to": provider.contract_addr,
                     "data": data,
                     "gas": "0x4C4B40", // 5,000,000 gas (less than block gas limit)
                     "gasPrice": gas_price,
                     "value": "0x0"
                 },
-                "latest", // or any other block number or tag
+                block.to_param(),
                 overrides
             ],
-            "id": chain_list.get(network)
+            "id": 1
         });
 
-        let client = Client::new();
-        let response = client
-            .post(api_url)
-            .header("Content-Type", "application/json")
-            .body(payload.to_string())
-            .send()
-            .await?;
-
-        let response_body: serde_json::Value = response.json().await?;
+        let response_body = Self::post(&provider.rpc_url, &payload).await?;
         let result = match response_body["result"].as_str() {
-            Some(value) => value,
+            Some(value) => value.trim_start_matches("0x"),
             None => {
                 // Handle the case when "result" is missing or not a string
-                eprintln!("Unexpected response: {:?}", response_body.get("error").unwrap().get("message").unwrap());
-                return Err("Invalid response format".into());
+                return Err(ExtractorError::Rpc(format!("eth_call failed: {:?}", response_body["error"]["message"])));
             }
         };
+        if result.len() < slots.len() * 64 {
+            return Err(ExtractorError::Rpc(format!("eth_call returned {} bytes for {} slots", result.len() / 2, slots.len())));
+        }
 
         // Parse the result and return the values as a HashMap mapping astId to value
         let mut values: HashMap<usize, String> = HashMap::new();
         let mut index = 0;
-        for (ast_id, _) in slots {
+        for ast_id in slots.keys() {
             let value = &result[index..index + 64];
             values.insert(*ast_id, value.to_string());
             index += 64;
@@ -73,12 +72,109 @@ impl EthCall {
         Ok(values)
     }
 
-    pub fn get_chain_list() -> HashMap<String, i64> {
-        let file_path = Path::new("chainIds.json");
-        let file_content = fs::read_to_string(file_path).expect("Unable to read file");
-        let id_to_network: Value = serde_json::from_str(&file_content).expect("Unable to parse JSON");
-        
-        let name_to_id: HashMap<String, i64> = id_to_network.as_object().unwrap().iter().map(|(k, v)| (v.as_str().unwrap().to_string(), k.parse::<i64>().unwrap())).collect();
-        name_to_id
+    pub async fn get_values_by_storage_at(slots: &HashMap<usize, String>, provider: &Provider, block: &BlockId) -> Result<HashMap<usize, String>, ExtractorError> {
+        let ast_ids: Vec<usize> = slots.keys().cloned().collect();
+
+        let mut values: HashMap<usize, String> = HashMap::new();
+        for chunk in ast_ids.chunks(STORAGE_AT_BATCH_SIZE) {
+            // JSON-RPC id is the position in this chunk, so responses can come back in any order.
+            let payload: Vec<Value> = chunk.iter().enumerate().map(|(i, ast_id)| json!({
+                "jsonrpc": "2.0",
+                "method": "eth_getStorageAt",
+                "params": [
+                    provider.contract_addr,
+                    format!("0x{}", slots[ast_id].trim_start_matches("0x")),
+                    block.to_param()
+                ],
+                "id": i
+            })).collect();
+
+            let response_body = Self::post(&provider.rpc_url, &Value::Array(payload)).await?;
+            let responses = match response_body.as_array() {
+                Some(responses) => responses,
+                None => {
+                    return Err(ExtractorError::Rpc(format!("Invalid batch response: {:?}", response_body["error"]["message"])));
+                }
+            };
+
+            for response in responses {
+                let index = match response["id"].as_u64() {
+                    Some(index) if (index as usize) < chunk.len() => index as usize,
+                    _ => return Err(ExtractorError::Rpc(format!("Unknown id in batch response: {:?}", response["id"]))),
+                };
+                let value = match response["result"].as_str() {
+                    Some(value) => value.trim_start_matches("0x"),
+                    None => return Err(ExtractorError::Rpc(format!("eth_getStorageAt failed: {:?}", response["error"]["message"]))),
+                };
+                values.insert(chunk[index], format!("{:0>64}", value));
+            }
+        }
+
+        if values.len() != slots.len() {
+            return Err(ExtractorError::Rpc(format!("eth_getStorageAt returned {} of {} slots", values.len(), slots.len())));
+        }
+        Ok(values)
+    }
+
+    pub async fn get_chain_id(rpc_url: &str) -> Result<i64, ExtractorError> {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "method": "eth_chainId",
+            "params": [],
+            "id": 1
+        });
+
+        let response_body = Self::post(rpc_url, &payload).await?;
+        match response_body["result"].as_str() {
+            Some(value) => i64::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|e| ExtractorError::Rpc(format!("Invalid eth_chainId {}: {}", value, e))),
+            None => Err(ExtractorError::Rpc(format!("eth_chainId failed: {:?}", response_body["error"]["message"]))),
+        }
+    }
+
+    pub async fn get_block_number(rpc_url: &str) -> Result<u64, ExtractorError> {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "method": "eth_blockNumber",
+            "params": [],
+            "id": 1
+        });
+
+        let response_body = Self::post(rpc_url, &payload).await?;
+        match response_body["result"].as_str() {
+            Some(value) => u64::from_str_radix(value.trim_start_matches("0x"), 16).map_err(|e| ExtractorError::Rpc(format!("Invalid eth_blockNumber {}: {}", value, e))),
+            None => Err(ExtractorError::Rpc(format!("eth_blockNumber failed: {:?}", response_body["error"]["message"]))),
+        }
+    }
+
+    async fn post(rpc_url: &str, payload: &Value) -> Result<Value, ExtractorError> {
+        let client = Client::new();
+        let response = client
+            .post(rpc_url)
+            .header("Content-Type", "application/json")
+            .body(payload.to_string())
+            .send()
+            .await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub fn get_network_name(chain_id: i64) -> Result<Option<String>, ExtractorError> {
+        Ok(Self::get_chain_list()?
+            .into_iter()
+            .find(|(_, id)| *id == chain_id)
+            .map(|(network, _)| network))
+    }
+
+    pub fn get_chain_list() -> Result<HashMap<String, i64>, ExtractorError> {
+        let id_to_network: Value = serde_json::from_str(CHAIN_IDS).map_err(|e| ExtractorError::Config(format!("Unable to parse chainIds.json: {}", e)))?;
+
+        let mut name_to_id: HashMap<String, i64> = HashMap::new();
+        for (id, network) in id_to_network.as_object().ok_or(ExtractorError::Config("chainIds.json is not an object".to_string()))? {
+            match (network.as_str(), id.parse::<i64>()) {
+                (Some(network), Ok(id)) => { name_to_id.insert(network.to_string(), id); },
+                _ => return Err(ExtractorError::Config(format!("Invalid chainIds.json entry: {}: {}", id, network))),
+            }
+        }
+        Ok(name_to_id)
     }
 }
\ No newline at end of file
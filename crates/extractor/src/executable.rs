@@ -1,38 +1,27 @@
-use super::compiler::Compiler;
-use super::extractor::Extractor;
-use super::executor::Executor;
 use super::registry::Registry;
 // use super::executable::Executable;
 use super::config_util::ConfigUtil;
 use super::type_kind::TypeKind;
-use super::eth_call::EthCall;
-use super::perf_expression_evaluator::PerfExpressionEvaluator;
 use super::ast_node::ASTNode;
-use super::context::Context;
+use super::slot_util::SlotUtil;
+use super::error::ExtractorError;
 
 
-use std::collections::HashMap;
-use std::process::Command;
-use serde_json::Value;
-use std::rc::Rc;
-use std::cell::RefCell;
 use ethers::utils::keccak256;
 use ethers::utils::hex;
-use std::error::Error;
 use num_bigint::BigUint;
-use num_traits::{ToPrimitive, Zero};
 
 
 #[allow(dead_code)]
 #[derive(Clone,Debug)]
 pub struct Executable {
     pub id: usize,
-    pub name: String,
+    pub name: String, // a root is named after its schema struct
     pub fulltype: String,
     pub belongs_to: Option<Box<Executable>>,
     pub type_kind: TypeKind,
     pub value_type: String,
-    offset: usize,
+    pub offset: usize,
     relative_slot: String,
     pub mapping_key: Option<String>,
     pub key_type: Option<String>,
@@ -40,6 +29,7 @@ pub struct Executable {
 
 
 impl Executable {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: usize,
         name: String,
@@ -69,27 +59,30 @@ impl Executable {
     pub fn is_iterish(&self) -> bool {
         self.type_kind.is_iterish()
     }
-    pub fn children(&self, registry: &Registry, indices: Option<Vec<String>>) -> Result<Vec<Executable>, Box<dyn Error>> {
+    pub fn children(&self, registry: &Registry, indices: Option<Vec<String>>) -> Result<Vec<Executable>, ExtractorError> {
         let mut children = Vec::new();
 
-        let current_node = &registry.visit_ast(&self.fulltype).unwrap();
+        let current_node = &registry.type_info(&self.fulltype)?;
 
 
         match current_node.get("members") {
             Some(_members) => {
-                for _member in _members.as_array().unwrap() {
-                    let fulltype = _member.get("type").unwrap().to_string();
-                    let _ast_id = u64::from_le_bytes(keccak256(&format!("{}{}", fulltype, "").as_bytes())[..8].try_into().unwrap()) as usize;
+                for _member in _members.as_array().ok_or(ExtractorError::Layout(format!("{} has invalid members", self.fulltype)))? {
+                    let (fulltype, label, offset, slot) = match (_member["type"].as_str(), _member["label"].as_str(), _member["offset"].as_u64(), _member["slot"].as_str()) {
+                        (Some(fulltype), Some(label), Some(offset), Some(slot)) => (fulltype.to_string(), label.to_string(), offset as usize, slot.to_string()),
+                        _ => return Err(ExtractorError::Layout(format!("{} has an invalid member: {}", self.fulltype, _member))),
+                    };
+                    let _ast_id = Self::child_id(self.id, &label);
 
                     let new_executable = Executable::new(
                         _ast_id,
-                        _member.get("label").unwrap().to_string(), // member_fulltype of the current node
+                        label, // member_fulltype of the current node
                         fulltype.clone(), // fulltype
                         Some(Box::new(self.clone())), // set the belongs_to to the current executable
-                        ASTNode::type_kind(&fulltype.clone()), // type kind of the current node
+                        ASTNode::type_kind(&fulltype)?, // type kind of the current node
                         fulltype.clone(), // type of the current node
-                        _member.get("offset").unwrap().as_u64().unwrap() as usize, // offset of the current node
-                        _member.get("slot").unwrap().to_string(), // slot of the current node
+                        offset, // offset of the current node
+                        slot, // slot of the current node
                         None,
                         None,
                     );
@@ -98,28 +91,65 @@ impl Executable {
                 Ok(children)
             },
             None => {
-                if self.is_iterish() && indices.unwrap().len() > 0 {
-                    for i in indices.unwrap() {
-                        let key_type = current_node.get("key_type").unwrap().to_string();
-                        let value_type = current_node.get("value_type").unwrap().to_string();
-                        let _ast_id = u64::from_le_bytes(keccak256(&format!("{}{}", value_type, i).as_bytes())[..8].try_into().unwrap()) as usize;
-                        let current_node = registry.visit_ast(&value_type).unwrap();
+                let indices = match indices {
+                    Some(indices) => indices,
+                    None => registry.iter_indices(self.id),
+                };
+                if self.type_kind == TypeKind::Mapping && !indices.is_empty() {
+                    // solc storageLayout: {"encoding": "mapping", "key": <type>, "value": <type>}
+                    let (key_type, value_type) = match (current_node["key"].as_str(), current_node["value"].as_str()) {
+                        (Some(key_type), Some(value_type)) => (key_type.to_string(), value_type.to_string()),
+                        _ => return Err(ExtractorError::Layout(format!("{} has no key/value types", self.fulltype))),
+                    };
+                    let value_label = registry.label(&value_type)?;
+
+                    for i in indices {
+                        let _ast_id = Self::child_id(self.id, &i);
 
                         let new_executable = Executable::new(
                             _ast_id,
-                            current_node.get("label").unwrap().to_string(), // member_fulltype of the current node
+                            value_label.clone(), // member_fulltype of the current node
                             value_type.clone(), // fulltype
                             Some(Box::new(self.clone())), // set the belongs_to to the current executable
-                            ASTNode::type_kind(&value_type.clone()), // type kind of the current node
+                            ASTNode::type_kind(&value_type)?, // type kind of the current node
                             value_type.clone(), // type of the current node
-                            current_node.get("offset").unwrap().as_u64().unwrap() as usize, // offset of the current node
-                            current_node.get("slot").unwrap().to_string(), // slot of the current node
+                            0, // a mapping value always starts a new slot
+                            0.to_string(), // the slot itself is derived from the key
                             Some(i),
                             Some(key_type.clone()),
                         );
                         children.push(new_executable);
                     }
                     Ok(children)
+                } else if matches!(self.type_kind, TypeKind::Array | TypeKind::FixedArray(_)) && !indices.is_empty() {
+                    // solc storageLayout: {"encoding": "dynamic_array" | "inplace", "base": <type>}
+                    let value_type = match current_node["base"].as_str() {
+                        Some(value_type) => value_type.to_string(),
+                        None => return Err(ExtractorError::Layout(format!("{} has no base type", self.fulltype))),
+                    };
+                    let value_label = registry.label(&value_type)?;
+                    let number_of_bytes = registry.number_of_bytes(&value_type)?;
+
+                    for i in indices {
+                        let _ast_id = Self::child_id(self.id, &i);
+                        let index = i.parse::<usize>().map_err(|e| ExtractorError::Slot(format!("Invalid array index {}: {}", i, e)))?;
+                        let (_, offset) = SlotUtil::element_position(index, number_of_bytes);
+
+                        let new_executable = Executable::new(
+                            _ast_id,
+                            value_label.clone(), // member_fulltype of the current node
+                            value_type.clone(), // fulltype
+                            Some(Box::new(self.clone())), // set the belongs_to to the current executable
+                            ASTNode::type_kind(&value_type)?, // type kind of the current node
+                            value_type.clone(), // type of the current node
+                            offset, // small elements are packed
+                            0.to_string(), // the slot itself is derived from the index
+                            Some(i),
+                            None,
+                        );
+                        children.push(new_executable);
+                    }
+                    Ok(children)
                 } else {
                     // primitive doesn't have children
                     Ok(vec!())
@@ -129,6 +159,12 @@ impl Executable {
     }
  
 
+    // Instance id of a child. Derived from the parent id so same-typed siblings and
+    // identical keys under different parents never collide.
+    fn child_id(parent_id: usize, label_or_key: &str) -> usize {
+        u64::from_le_bytes(keccak256(format!("{}.{}", parent_id, label_or_key).as_bytes())[..8].try_into().unwrap()) as usize
+    }
+
     pub fn is_iter_readied(&self, registry: &Registry) -> bool {
         let (_, to) = match registry.iterish_from_to.get(&self.id) {
             Some((from, to)) => (*from, *to),
@@ -138,47 +174,41 @@ impl Executable {
         };
 
         // If the iterator's `to` field is empty (likely a mapping)
-        if to == 0 {
-            false
-        } else {
-            true
-        }
+        to != 0
     }
     
-    pub fn calculate_absolute_slot(&self, registry: &Registry) -> String {
-        match &self.belongs_to {
-            Some(belongs_to) => {
-                match registry.absolute_slots.get(&belongs_to.id) {
-                    Some(belongs_to_absolute_slot) => {
-                        let combined_slot = if belongs_to.is_iterish() {
-                            match &self.mapping_key {
-                                Some(mapping_key) => {
-                                    let iterable_absolute_slot = format!("{}{}", mapping_key, belongs_to_absolute_slot.trim_start_matches("0x"));
-                                    let hash_combined = hex::encode(ethers::utils::keccak256(iterable_absolute_slot.as_bytes()));
-                                    hash_combined
-                                },
-                                None => {
-                                    panic!("No absolute_slot: {}", belongs_to.id);
-                                }            
-                            }
-                        } else {                            
-                            match Executable::add_usize_to_32bytes(&belongs_to_absolute_slot.clone().trim_start_matches("0x"), &self.relative_slot.clone()) {
-                                Ok(abs_slot) => abs_slot,
-                                Err(err) => panic!("{}",err),
-                            }
-                        };
-                        combined_slot
-                    },
-                    None => {
-                        panic!("No absolute_slot: {}", belongs_to.id);
-                    }            
-                }
-            },
-            None => {
-                panic!("No belongs_to: {}", self.id);
-            }
-        }
+    pub fn calculate_absolute_slot(&self, registry: &Registry) -> Result<String, ExtractorError> {
+        let belongs_to = match &self.belongs_to {
+            Some(belongs_to) => belongs_to,
+            None => return Err(ExtractorError::Slot(format!("No belongs_to: {}", self.id))),
+        };
+        let belongs_to_absolute_slot = match registry.absolute_slots.get(&belongs_to.id) {
+            Some(belongs_to_absolute_slot) => belongs_to_absolute_slot,
+            None => return Err(ExtractorError::Slot(format!("No absolute_slot: {}", belongs_to.id))),
+        };
 
+        let combined_slot = if belongs_to.type_kind == TypeKind::Mapping {
+            match (&self.mapping_key, &self.key_type) {
+                (Some(mapping_key), Some(key_type)) => SlotUtil::mapping_slot(mapping_key, key_type, registry.number_of_bytes(key_type).ok(), belongs_to_absolute_slot),
+                _ => return Err(ExtractorError::Slot(format!("No mapping_key or key_type: {}", self.id))),
+            }
+        } else if matches!(belongs_to.type_kind, TypeKind::Array | TypeKind::FixedArray(_)) {
+            let index = match &self.mapping_key {
+                Some(index) => index.parse::<usize>().map_err(|e| ExtractorError::Slot(format!("Invalid array index {}: {}", index, e)))?,
+                None => return Err(ExtractorError::Slot(format!("No array index: {}", self.id))),
+            };
+            let number_of_bytes = registry.number_of_bytes(&self.fulltype)?;
+            let (slot_delta, _) = SlotUtil::element_position(index, number_of_bytes);
+            // dynamic array elements start at keccak256(p), fixed ones inline from p
+            let first_element_slot = match belongs_to.type_kind {
+                TypeKind::Array => SlotUtil::dynamic_array_data_slot(belongs_to_absolute_slot),
+                _ => Ok(belongs_to_absolute_slot.trim_start_matches("0x").to_string()),
+            };
+            first_element_slot.and_then(|slot| SlotUtil::add_to_slot(&slot, slot_delta))
+        } else {
+            Executable::add_usize_to_32bytes(belongs_to_absolute_slot.clone().trim_start_matches("0x"), &self.relative_slot.clone())
+        };
+        combined_slot.map_err(ExtractorError::Slot)
     }
 
     fn add_usize_to_32bytes(value: &str, number: &str) -> Result<String, String> {
@@ -247,10 +277,7 @@ impl Executable {
                     _paths.push(e.name);
                 }
             } else {
-                let regex = regex::Regex::new(r"t_struct\((\w+)\)\d{3}_storage").unwrap();
-                let captures = regex.captures(&e.value_type).unwrap();
-                let struct_name = captures.get(1).unwrap().as_str().to_string();
-                _paths.push(struct_name.clone());
+                _paths.push(e.name);
             }
         }
         _paths.push(self.name.replace("\"", ""));
@@ -263,19 +290,19 @@ impl Executable {
 
         for e in _ancestors {
             if e.belongs_to.is_some() {
-                if e.mapping_key.is_some() {
-                    _paths.push(e.mapping_key.unwrap());
+                if let Some(mapping_key) = e.mapping_key {
+                    _paths.push(mapping_key);
                 } else {
                     _paths.push(e.name);
                 }
             } else {
-                let regex = regex::Regex::new(r"t_struct\((\w+)\)\d{3}_storage").unwrap();
-                let captures = regex.captures(&e.value_type).unwrap();
-                let struct_name = captures.get(1).unwrap().as_str().to_string();
-                _paths.push(struct_name.clone());
+                _paths.push(e.name);
             }
         }
-        _paths.push(self.name.replace("\"", ""));
+        match &self.mapping_key {
+            Some(mapping_key) => _paths.push(mapping_key.clone()), // an element is named by its key/index
+            None => _paths.push(self.name.replace("\"", "")),
+        }
         _paths
     }
     pub fn paths_in_conf(&self) -> Vec<String> {
@@ -290,10 +317,7 @@ impl Executable {
                     _paths.push(e.name);
                 }
             } else {
-                let regex = regex::Regex::new(r"t_struct\((\w+)\)\d{3}_storage").unwrap();
-                let captures = regex.captures(&e.value_type).unwrap();
-                let struct_name = captures.get(1).unwrap().as_str().to_string();
-                _paths.push(struct_name.clone());
+                _paths.push(e.name);
             }
         }
         _paths.push(self.name.replace("\"", ""));
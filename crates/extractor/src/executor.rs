@@ -1,26 +1,14 @@
-use super::compiler::Compiler;
-use super::extractor::Extractor;
 // use super::executor::Executor;
-use super::registry::Registry;
 use super::executable::Executable;
-use super::config_util::ConfigUtil;
 use super::type_kind::TypeKind;
-use super::eth_call::EthCall;
-use super::perf_expression_evaluator::PerfExpressionEvaluator;
-use super::ast_node::ASTNode;
 use super::context::Context;
+use super::block_id::BlockId;
+use super::error::ExtractorError;
 
 
 use std::result::Result;
 use std::result::Result::{Ok, Err};
-use std::error::Error;
-use std::future::Future;
 use std::collections::HashMap;
-use std::process::Command;
-use serde_json::Value;
-use std::rc::Rc;
-use std::cell::RefCell;
-use std::env;
 
 
 pub struct Executor;
@@ -28,18 +16,22 @@ pub struct Executor;
 impl Executor {
 
     #[allow(unused_mut)]
-    pub async fn bulk_exec_and_reload(step: usize, registry: &mut Registry) -> Result<(), Box<dyn Error>> {
+    pub async fn bulk_exec_and_reload(step: usize, context: &mut Context, block: &BlockId) -> Result<(), ExtractorError> {
+        let registry = &mut context.registry;
 
         let mut absolute_slots: HashMap<usize, String> = HashMap::new();
         let mut primitives: HashMap<usize, Executable> = HashMap::new();
         let mut pending_fillable_iterish: HashMap<usize, Executable> = HashMap::new();
         let mut filled_queueable_iterish: HashMap<usize, Executable> = HashMap::new();
+        let mut arrays: HashMap<usize, Executable> = HashMap::new();
 
 
         // [exec]
         // - get absolute_slot
         // - get value
         // - preserve them
+        // Note: a failing node is skipped with its subtree, the rest of the step goes on
+        let mut executing: Vec<Executable> = Vec::new();
         for e in registry.queue_per_step[step].clone() {
             // match e.belongs_to {
             //     Some(ref belongs_to) => {
@@ -49,31 +41,49 @@ impl Executor {
             //         println!("parent: ---  e:{:?} {:?}", e.fulltype, e.name);
             //     }
             // }
-            absolute_slots.insert(e.id, e.clone().calculate_absolute_slot(&registry));
+            match e.calculate_absolute_slot(registry) {
+                Ok(absolute_slot) => {
+                    absolute_slots.insert(e.id, absolute_slot);
+                    executing.push(e);
+                },
+                Err(err) => {
+                    registry.skip(&e, err);
+                },
+            }
         }
         registry.bulk_set_absolute_slots(&absolute_slots); // Note: use it for knowing parent slot
 
-        let _contract_addr = match env::var("CONTRACT_ADDR") {
-            Ok(addr) => addr,
-            Err(_) => panic!("{}", "CONTRACT_ADDR was not provided."),
-        };
-        let _contract_code = match env::var("CONTRACT_CODE") {
-            Ok(code) => code,
-            Err(_) => panic!("{}", "CONTRACT_CODE was not provided."),
-        };
-        let values = EthCall::get_values_by_slots(&absolute_slots, "mainnet", &_contract_addr, &_contract_code).await?;
-        registry.bulk_save_values(values.clone());
-        registry.bulk_save_visited(registry.queue_per_step[step].clone());
+        // Note: members packed into one slot (and a struct sharing its first member's slot) are read once
+        let unread_slots = registry.unread_slots(&absolute_slots);
+        let words = context.reader.read_slots(&unread_slots, block).await?;
+        registry.bulk_save_words(&unread_slots, words);
+
+        // Long string/bytes keep their data from keccak256(slot). Read it now, at the same block.
+        let payload_slots = registry.payload_slots(&executing);
+        let unread_payload_slots = registry.unread_slots(&payload_slots);
+        if !unread_payload_slots.is_empty() {
+            let payload_words = context.reader.read_slots(&unread_payload_slots, block).await?;
+            registry.bulk_save_words(&unread_payload_slots, payload_words);
+        }
+
+        registry
+            .bulk_save_values(&executing)
+            .bulk_decode_values(&executing)
+            .bulk_save_visited(executing.clone());
 
 
         // [reload]
         // - enqueue executables by each type for next step
-        for e in registry.queue_per_step[step].clone() {
+        for e in executing.iter().filter(|e| !registry.is_skipped(e.id)) {
             if e.type_kind == TypeKind::Primitive {
                 // If the executable is a primitive, push it to the output
                 primitives.insert(e.id, e.clone());
+            } else if matches!(e.type_kind, TypeKind::Array | TypeKind::FixedArray(_)) {
+                // Arrays know their length right after exec: from the slot (dynamic) or the type (fixed).
+                arrays.insert(e.id, e.clone());
+                filled_queueable_iterish.insert(e.id, e.clone());
             } else if e.is_iterish() {
-                if e.is_iter_readied(&registry) {
+                if e.is_iter_readied(registry) {
                     filled_queueable_iterish.insert(e.id, e.clone());
                 } else {
                     pending_fillable_iterish.insert(e.id, e.clone());
@@ -86,6 +96,7 @@ impl Executor {
         registry
             .set_primitives(primitives.clone())
             .bulk_fill_from_to(&pending_fillable_iterish)
+            .bulk_fill_array_lengths(&arrays, context.config.limits.max_array_length)
             .bulk_enqueue_execution(step+1, pending_fillable_iterish.clone())
             .bulk_enqueue_children_execution(step+1, &filled_queueable_iterish);
 
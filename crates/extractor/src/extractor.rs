@@ -1,20 +1,19 @@
-use super::compiler::Compiler;
 use super::executor::Executor;
 use super::registry::Registry;
 use super::executable::Executable;
-use super::config_util::ConfigUtil;
 use super::type_kind::TypeKind;
-use super::eth_call::EthCall;
-use super::perf_expression_evaluator::PerfExpressionEvaluator;
-use super::ast_node::ASTNode;
+use super::type_id::{Location, TypeId};
 use super::context::Context;
+use super::error::ExtractorError;
+use super::storage_layout::StorageLayout;
+use super::slot_reader::SlotReader;
+use super::output_tree::OutputTree;
+use super::registry::Constraint;
+use super::indexer_config::IndexerConfig;
 
 use std::collections::HashMap;
-use std::process::Command;
 use serde_json::Value;
 use std::rc::Rc;
-use std::cell::RefCell;
-use std::mem;
 
 pub struct Extractor {
     state: ExtractorState,
@@ -35,31 +34,42 @@ impl Extractor {
         }
     }
 
-    pub fn init_members_from_compiler(&mut self, base_slots_index: &HashMap<String,String>) {
-        let mut base_slots: Vec<(String, Value, String)> = Vec::new();
-        // println!("{:?}", self.state.context.registry.types.clone());
-        for (_type, _value) in self.state.context.registry.types.as_object().unwrap() {
-            for (_baseslot_name, _slot) in base_slots_index {
-                if _type.contains(_baseslot_name) {
-                    base_slots.push((_type.clone(), _value.clone(), _slot.clone()));
-                }
-            }
-        }
+    // Context over a compiled layout, with the schema's base slots queued for step 0.
+    pub fn from_layout(layout: &StorageLayout, constraints: HashMap<usize, Constraint>, reader: Rc<dyn SlotReader>, config: &IndexerConfig) -> Result<Self, ExtractorError> {
+        let mut extractor = Self::new(Context {
+            registry: Registry::new(layout.blob.clone(), constraints),
+            reader,
+            config: config.clone(),
+        });
+        extractor.state.context.registry.commit = layout.commit.clone();
+        extractor.init_members_from_compiler(&layout.base_slots)?;
+        Ok(extractor)
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.state.context.registry
+    }
+
+    pub fn output_tree(&self) -> Value {
+        OutputTree::build(&self.state.context.registry)
+    }
 
+    pub fn init_members_from_compiler(&mut self, base_slots_index: &HashMap<String,String>) -> Result<(), ExtractorError> {
+        let mut names: Vec<&String> = base_slots_index.keys().collect();
+        names.sort();
 
         // Create Member objects from base_slots and storage_layout
         let mut i = 9999999999; // to avoid astId conflict
         let mut initial_members = HashMap::new();
         let mut absolute_slots = HashMap::new();
-        for (_type, slot_info, _slot) in base_slots {
-            let label = slot_info["label"].as_str().unwrap();
-            let fulltype = _type;
+        for name in names {
+            let fulltype = self.root_type_id(name)?;
             let type_kind = TypeKind::NaiveStruct;
 
             let member = Executable::new(
                 i, // astId
-                label.to_string(), // label of the current node
-                String::from(fulltype.clone()), // fulltype
+                name.clone(), // the schema struct name
+                fulltype.clone(), // fulltype
                 None, // Pass self as the belongs_to parameter
                 type_kind,
                 fulltype.clone(),
@@ -69,42 +79,385 @@ impl Extractor {
                 None, // Initialize iter as None, it will be populated later if needed
             );
             initial_members.insert(i, member.clone());
-            absolute_slots.insert(i, _slot.clone());
-            
+            absolute_slots.insert(i, base_slots_index[name].clone());
+
 
             i -= 1;
         }
         self.state.context.registry.bulk_set_absolute_slots(&absolute_slots); // Note: use it for knowing parent slot
         self.state.context.registry.bulk_enqueue_children_execution(0, &initial_members); // Note: use it for knowing parent slot
-
+        Ok(())
+    }
+    // The type of the schema struct `name`: its entry in the layout's storage list, else t_struct(<name>)<astId>_storage.
+    // Note: a substring match would also take t_struct(<name>Meta)..., arrays and mappings of the struct as roots
+    fn root_type_id(&self, name: &str) -> Result<String, ExtractorError> {
+        let registry = &self.state.context.registry;
+        let listed = registry.storage.as_array().into_iter().flatten()
+            .find(|entry| entry["label"] == name)
+            .and_then(|entry| entry["type"].as_str());
+        if let Some(type_id) = listed {
+            return Ok(type_id.to_string());
+        }
+        let types = registry.types.as_object().ok_or(ExtractorError::Layout("storageLayout has no types".to_string()))?;
+        types.keys()
+            .find(|type_id| matches!(TypeId::parse(type_id), Ok(TypeId::Struct { name: struct_name, location: Some(Location::Storage), .. }) if struct_name == name))
+            .cloned()
+            .ok_or(ExtractorError::Layout(format!("No struct {} in the storageLayout types", name)))
     }
-    pub async fn listen(&mut self) {
-        self.scan_contract().await;
+
+    pub async fn listen(&mut self) -> Result<(), ExtractorError> {
+        self.state.context.reader.verify().await?;
+        self.scan_contract().await
     }
 
 
-    pub async fn scan_contract(&mut self) {
+    pub async fn scan_contract(&mut self) -> Result<(), ExtractorError> {
+        let block = self.state.context.reader.resolve_block().await?;
+        self.state.context.registry.block = Some(block.clone());
+
         while 
             self.state.context.registry.queue_per_step.len() > self.state.step // maybe no queueable and no insertion to the next step
-            && self.state.context.registry.queue_per_step[self.state.step].len() > 0 // maybe won't be called
-            && self.state.step <= 15
+            && !self.state.context.registry.queue_per_step[self.state.step].is_empty() // maybe won't be called
+            && self.state.step < self.state.context.config.limits.max_steps
         {
-            match Executor::bulk_exec_and_reload(self.state.step, &mut self.state.context.registry).await {
-                Ok(()) => (),
-                Err(err) => {
-                    println!("Error reloading context: {}", err);
-                    break;
-                }
-            };
+            Executor::bulk_exec_and_reload(self.state.step, &mut self.state.context, &block).await?;
 
             self.state.step += 1;
         }
-
-        println!("absolute_slots ::: {:?}", self.state.context.registry.absolute_slots.clone());
-        println!("values ::: {:?}", self.state.context.registry.values.clone());
-
+        Ok(())
     }
   
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_id::BlockId;
+    use crate::slot_reader::MemoryReader;
+    use crate::slot_util::SlotUtil;
+    use crate::test_support::{self, BASE_SLOT};
+    use serde_json::json;
+    use ethers::utils::hex;
+
+    // The schema struct Foo (t_struct(Foo)1_storage) with `members`, plus `others` and the elementary types.
+    fn foo_types(number_of_bytes: usize, members: Value, others: Value) -> Value {
+        let mut types = test_support::types(others);
+        types["t_struct(Foo)1_storage"] = json!({ "encoding": "inplace", "label": "struct Schema.Foo", "numberOfBytes": number_of_bytes.to_string(), "members": members });
+        types
+    }
+
+    // Scans `storage` at block 1, from Foo at BASE_SLOT.
+    async fn scan_fixture(types: Value, storage: HashMap<String, String>) -> Registry {
+        let mut extractor = Extractor::new(Context {
+            registry: Registry::new(test_support::layout(types, &[]).blob, HashMap::new()),
+            reader: Rc::new(MemoryReader::new(storage, BlockId::Number(1))),
+            config: IndexerConfig::default(),
+        });
+        extractor.init_members_from_compiler(&HashMap::from([("Foo".to_string(), BASE_SLOT.to_string())])).unwrap();
+        extractor.scan_contract().await.unwrap();
+        extractor.state.context.registry
+    }
+
+    fn scanned_values(registry: &Registry) -> Vec<(String, String)> {
+        let mut values: Vec<(String, String)> = registry.output_flatten.values()
+            .map(|e| (e.fullname(), registry.values[&e.id].trim_start_matches('0').to_string()))
+            .collect();
+        values.sort();
+        values
+    }
+
+    fn skipped(registry: &Registry) -> Vec<(String, ExtractorError)> {
+        registry.skipped.values().cloned().collect()
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_with_fixture_storage() {
+        let types = foo_types(96, json!([
+            { "astId": 2, "label": "a", "offset": 0, "slot": "0", "type": "t_uint256" },
+            { "astId": 3, "label": "b", "offset": 0, "slot": "1", "type": "t_uint256" },
+            { "astId": 4, "label": "bar", "offset": 0, "slot": "2", "type": "t_struct(Bar)5_storage" }
+        ]), json!({
+            "t_struct(Bar)5_storage": {
+                "encoding": "inplace",
+                "label": "struct Schema.Bar",
+                "numberOfBytes": "32",
+                "members": [
+                    { "astId": 6, "label": "c", "offset": 0, "slot": "0", "type": "t_uint256" }
+                ]
+            }
+        }));
+        let storage = HashMap::from([
+            ("0x100".to_string(), "0x2a".to_string()),
+            ("0x101".to_string(), "0x2b".to_string()),
+            ("0x102".to_string(), "0x2c".to_string()),
+        ]);
+        let registry = scan_fixture(types, storage).await;
+
+        assert_eq!(registry.block, Some(BlockId::Number(1)));
+        assert_eq!(scanned_values(&registry), vec![
+            ("Foo.a".to_string(), "2a".to_string()),
+            ("Foo.b".to_string(), "2b".to_string()),
+            ("Foo.bar.c".to_string(), "2c".to_string()),
+        ]);
+        let mut decoded: Vec<String> = registry.decoded.values().map(|v| v.to_string()).collect();
+        decoded.sort();
+        assert_eq!(decoded, vec!["42", "43", "44"]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_roots_only_the_base_slot_struct() {
+        // Every other id here contains "Foo" too
+        let types = foo_types(32, json!([
+            { "astId": 2, "label": "a", "offset": 0, "slot": "0", "type": "t_uint256" }
+        ]), json!({
+            "t_struct(FooMeta)3_storage": {
+                "encoding": "inplace",
+                "label": "struct Schema.FooMeta",
+                "numberOfBytes": "32",
+                "members": [
+                    { "astId": 4, "label": "b", "offset": 0, "slot": "0", "type": "t_uint256" }
+                ]
+            },
+            "t_array(t_struct(Foo)1_storage)dyn_storage": { "encoding": "dynamic_array", "label": "struct Schema.Foo[]", "numberOfBytes": "32", "base": "t_struct(Foo)1_storage" },
+            "t_mapping(t_uint256,t_struct(Foo)1_storage)": { "encoding": "mapping", "label": "mapping(uint256 => struct Schema.Foo)", "numberOfBytes": "32", "key": "t_uint256", "value": "t_struct(Foo)1_storage" }
+        }));
+        let storage = HashMap::from([(BASE_SLOT.to_string(), "0x2a".to_string())]);
+        let registry = scan_fixture(types.clone(), storage.clone()).await;
+        assert_eq!(scanned_values(&registry), vec![("Foo.a".to_string(), "2a".to_string())]);
+
+        // With a storage list, its entry names the root type
+        let mut layout = test_support::layout(types, &[("Foo", BASE_SLOT)]);
+        layout.blob["storageLayout"]["storage"] = json!([{ "label": "Foo", "offset": 0, "slot": "256", "type": "t_struct(Foo)1_storage" }]);
+        let extractor = crate::scan(&layout, HashMap::new(), Rc::new(MemoryReader::new(storage, BlockId::Number(1))), &IndexerConfig::default()).await.unwrap();
+        assert_eq!(extractor.output_tree(), json!({ "Foo": { "a": "42" } }));
+
+        let layout = test_support::layout(json!({}), &[("Foo", BASE_SLOT)]);
+        assert!(Extractor::from_layout(&layout, HashMap::new(), Rc::new(MemoryReader::new(HashMap::new(), BlockId::Number(1))), &IndexerConfig::default()).is_err());
+    }
 
+    #[tokio::test]
+    async fn test_scan_names_a_root_after_its_struct_whatever_its_type_id() {
+        let mut types = foo_types(32, json!([
+            { "astId": 2, "label": "a", "offset": 0, "slot": "0", "type": "t_uint256" }
+        ]), json!({}));
+        types["t_struct(Foo)1_memory_ptr"] = types["t_struct(Foo)1_storage"].take();
+        let mut layout = test_support::layout(types, &[("Foo", BASE_SLOT)]);
+        layout.blob["storageLayout"]["storage"] = json!([{ "label": "Foo", "offset": 0, "slot": "256", "type": "t_struct(Foo)1_memory_ptr" }]);
+        let storage = HashMap::from([(BASE_SLOT.to_string(), "0x2a".to_string())]);
+        let extractor = crate::scan(&layout, HashMap::new(), Rc::new(MemoryReader::new(storage, BlockId::Number(1))), &IndexerConfig::default()).await.unwrap();
+
+        assert_eq!(extractor.output_tree(), json!({ "Foo": { "a": "42" } }));
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_with_dynamic_array_of_structs() {
+        let types = foo_types(32, json!([
+            { "astId": 2, "label": "pairs", "offset": 0, "slot": "0", "type": "t_array(t_struct(Pair)3_storage)dyn_storage" }
+        ]), json!({
+            "t_array(t_struct(Pair)3_storage)dyn_storage": {
+                "encoding": "dynamic_array",
+                "label": "struct Schema.Pair[]",
+                "numberOfBytes": "32",
+                "base": "t_struct(Pair)3_storage"
+            },
+            "t_struct(Pair)3_storage": {
+                "encoding": "inplace",
+                "label": "struct Schema.Pair",
+                "numberOfBytes": "64",
+                "members": [
+                    { "astId": 4, "label": "x", "offset": 0, "slot": "0", "type": "t_uint256" },
+                    { "astId": 5, "label": "y", "offset": 0, "slot": "1", "type": "t_uint256" }
+                ]
+            }
+        }));
+        // Pair takes 2 slots, so pairs[1].x lives at keccak256(p) + 2
+        let data_slot = SlotUtil::dynamic_array_data_slot(BASE_SLOT).unwrap();
+        let storage = HashMap::from([
+            (BASE_SLOT.to_string(), "0x2".to_string()),
+            (SlotUtil::add_to_slot(&data_slot, 0).unwrap(), "0xa0".to_string()),
+            (SlotUtil::add_to_slot(&data_slot, 1).unwrap(), "0xa1".to_string()),
+            (SlotUtil::add_to_slot(&data_slot, 2).unwrap(), "0xb0".to_string()),
+            (SlotUtil::add_to_slot(&data_slot, 3).unwrap(), "0xb1".to_string()),
+        ]);
+        let registry = scan_fixture(types, storage).await;
+
+        assert_eq!(scanned_values(&registry), vec![
+            ("Foo.pairs.0.x".to_string(), "a0".to_string()),
+            ("Foo.pairs.0.y".to_string(), "a1".to_string()),
+            ("Foo.pairs.1.x".to_string(), "b0".to_string()),
+            ("Foo.pairs.1.y".to_string(), "b1".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_with_packed_fixed_array() {
+        let types = foo_types(64, json!([
+            { "astId": 2, "label": "limits", "offset": 0, "slot": "0", "type": "t_array(t_uint128)3_storage" }
+        ]), json!({
+            "t_array(t_uint128)3_storage": {
+                "encoding": "inplace",
+                "label": "uint128[3]",
+                "numberOfBytes": "64",
+                "base": "t_uint128"
+            }
+        }));
+        // limits[0] and limits[1] share the base slot, limits[2] starts the next one
+        let storage = HashMap::from([
+            (BASE_SLOT.to_string(), format!("0x{:0>32}{:0>32}", "2", "1")),
+            (SlotUtil::add_to_slot(BASE_SLOT, 1).unwrap(), "0x3".to_string()),
+        ]);
+        let registry = scan_fixture(types, storage).await;
+
+        assert_eq!(scanned_values(&registry), vec![
+            ("Foo.limits.0".to_string(), "1".to_string()),
+            ("Foo.limits.1".to_string(), "2".to_string()),
+            ("Foo.limits.2".to_string(), "3".to_string()),
+        ]);
+        assert_eq!(registry.words.len(), 2);
+        let mut slots: Vec<(String, String)> = registry.output_flatten.values()
+            .map(|e| (e.fullname(), registry.absolute_slots[&e.id].clone()))
+            .collect();
+        slots.sort();
+        assert_eq!(slots, vec![
+            ("Foo.limits.0".to_string(), BASE_SLOT.trim_start_matches("0x").to_string()),
+            ("Foo.limits.1".to_string(), BASE_SLOT.trim_start_matches("0x").to_string()),
+            ("Foo.limits.2".to_string(), SlotUtil::add_to_slot(BASE_SLOT, 1).unwrap()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_decodes_user_defined_value_type() {
+        let types = foo_types(32, json!([
+            { "astId": 2, "label": "price", "offset": 0, "slot": "0", "type": "t_userDefinedValueType(Price)3" }
+        ]), json!({
+            "t_userDefinedValueType(Price)3": { "encoding": "inplace", "label": "Schema.Price", "numberOfBytes": "16", "underlying": "t_uint128" }
+        }));
+        let registry = scan_fixture(types, HashMap::from([(BASE_SLOT.to_string(), "0x2a".to_string())])).await;
+
+        assert_eq!(scanned_values(&registry), vec![("Foo.price".to_string(), "2a".to_string())]);
+        assert_eq!(OutputTree::build(&registry), json!({ "Foo": { "price": "42" } }));
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_with_short_and_long_strings() {
+        let types = foo_types(64, json!([
+            { "astId": 2, "label": "title", "offset": 0, "slot": "0", "type": "t_string_storage" },
+            { "astId": 3, "label": "body", "offset": 0, "slot": "1", "type": "t_string_storage" }
+        ]), json!({
+            "t_string_storage": { "encoding": "bytes", "label": "string", "numberOfBytes": "32" }
+        }));
+        let body = "a proposal body longer than one slot";
+        let body_slot = SlotUtil::add_to_slot(BASE_SLOT, 1).unwrap();
+        let body_data_slot = SlotUtil::dynamic_array_data_slot(&body_slot).unwrap();
+        let storage = HashMap::from([
+            (BASE_SLOT.to_string(), format!("{:0<62}0a", hex::encode("hello"))),
+            (body_slot.clone(), format!("{:x}", body.len() * 2 + 1)),
+            (body_data_slot.clone(), hex::encode(&body[..32])),
+            (SlotUtil::add_to_slot(&body_data_slot, 1).unwrap(), format!("{:0<64}", hex::encode(&body[32..]))),
+        ]);
+        let registry = scan_fixture(types, storage).await;
+
+        let mut decoded: Vec<(String, String)> = registry.output_flatten.values()
+            .map(|e| (e.fullname(), registry.decoded[&e.id].to_string()))
+            .collect();
+        decoded.sort();
+        assert_eq!(decoded, vec![
+            ("Foo.body".to_string(), body.to_string()),
+            ("Foo.title".to_string(), "hello".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_skips_failing_subtree() {
+        let types = foo_types(64, json!([
+            { "astId": 2, "label": "items", "offset": 0, "slot": "0", "type": "t_array(t_uint256)dyn_storage" },
+            { "astId": 3, "label": "count", "offset": 0, "slot": "1", "type": "t_uint256" }
+        ]), json!({
+            "t_array(t_uint256)dyn_storage": { "encoding": "dynamic_array", "label": "uint256[]", "numberOfBytes": "32", "base": "t_uint256" }
+        }));
+        // A length no array can have: items is dropped, count is still read
+        let storage = HashMap::from([
+            (BASE_SLOT.to_string(), format!("0x1{}", "0".repeat(60))),
+            (SlotUtil::add_to_slot(BASE_SLOT, 1).unwrap(), "0x7".to_string()),
+        ]);
+        let registry = scan_fixture(types, storage).await;
+
+        assert_eq!(scanned_values(&registry), vec![("Foo.count".to_string(), "7".to_string())]);
+        let skipped = skipped(&registry);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, "Foo.items");
+        assert!(matches!(skipped[0].1, ExtractorError::Slot(_)));
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_skips_array_longer_than_the_limit() {
+        let types = foo_types(64, json!([
+            { "astId": 2, "label": "items", "offset": 0, "slot": "0", "type": "t_array(t_uint256)dyn_storage" },
+            { "astId": 3, "label": "count", "offset": 0, "slot": "1", "type": "t_uint256" }
+        ]), json!({
+            "t_array(t_uint256)dyn_storage": { "encoding": "dynamic_array", "label": "uint256[]", "numberOfBytes": "32", "base": "t_uint256" }
+        }));
+        // 2^40 fits a usize, but no element is enqueued nor read
+        let storage = HashMap::from([
+            (BASE_SLOT.to_string(), format!("0x{:x}", 1u64 << 40)),
+            (SlotUtil::add_to_slot(BASE_SLOT, 1).unwrap(), "0x7".to_string()),
+        ]);
+        let registry = scan_fixture(types, storage).await;
+
+        assert_eq!(scanned_values(&registry), vec![("Foo.count".to_string(), "7".to_string())]);
+        assert!(registry.queue_per_step.iter().all(|queue| queue.len() < 3));
+        let skipped = skipped(&registry);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, "Foo.items");
+        assert!(skipped[0].1.to_string().contains("exceeds limits.max_array_length (100000)"));
+    }
+
+    #[tokio::test]
+    async fn test_scan_contract_skips_string_with_corrupt_length() {
+        let types = foo_types(64, json!([
+            { "astId": 2, "label": "title", "offset": 0, "slot": "0", "type": "t_string_storage" },
+            { "astId": 3, "label": "count", "offset": 0, "slot": "1", "type": "t_uint256" }
+        ]), json!({
+            "t_string_storage": { "encoding": "bytes", "label": "string", "numberOfBytes": "32" }
+        }));
+        // A short string can't be 127 bytes long: title is dropped instead of slicing past its word
+        let storage = HashMap::from([
+            (BASE_SLOT.to_string(), format!("{:0>64}", "fe")),
+            (SlotUtil::add_to_slot(BASE_SLOT, 1).unwrap(), "0x7".to_string()),
+        ]);
+        let registry = scan_fixture(types, storage).await;
+
+        assert_eq!(scanned_values(&registry), vec![("Foo.count".to_string(), "7".to_string())]);
+        let skipped = skipped(&registry);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, "Foo.title");
+        assert!(matches!(skipped[0].1, ExtractorError::Slot(_)));
+    }
+
+    #[tokio::test]
+    async fn test_scan_from_layout_builds_output_tree() {
+        let types = foo_types(64, json!([
+            { "astId": 2, "label": "owner", "offset": 0, "slot": "0", "type": "t_address" },
+            { "astId": 3, "label": "limits", "offset": 0, "slot": "1", "type": "t_array(t_uint128)2_storage" }
+        ]), json!({
+            "t_array(t_uint128)2_storage": { "encoding": "inplace", "label": "uint128[2]", "numberOfBytes": "32", "base": "t_uint128" }
+        }));
+        let layout = StorageLayout { commit: Some("0123abcd".to_string()), ..test_support::layout(types, &[("Foo", BASE_SLOT)]) };
+        let storage = HashMap::from([
+            (BASE_SLOT.to_string(), "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".to_string()),
+            (SlotUtil::add_to_slot(BASE_SLOT, 1).unwrap(), format!("0x{:0>32}{:0>32}", "2", "1")),
+        ]);
+        let extractor = crate::scan(&layout, HashMap::new(), Rc::new(MemoryReader::new(storage, BlockId::Number(7))), &IndexerConfig::default()).await.unwrap();
+
+        assert_eq!(extractor.output_tree(), json!({
+            "Foo": {
+                "owner": "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+                "limits": { "0": "1", "1": "2" }
+            }
+        }));
+        let document = OutputTree::document(extractor.registry());
+        assert_eq!(document["block"], "7");
+        assert_eq!(document["commit"], "0123abcd");
+        assert_eq!(document["skipped"], json!([]));
+    }
+}
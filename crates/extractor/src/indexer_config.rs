@@ -0,0 +1,342 @@
+use super::mc_repo_fetcher::MCRepoFetcher;
+use super::provider::{Provider, ReadStrategy, DEFAULT_CHAIN_ID, DEFAULT_RPC_URL};
+use super::block_id::BlockId;
+use super::error::ExtractorError;
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CONFIG_PATH: &str = "indexer.toml";
+
+// indexer.toml, loaded once at startup:
+//
+// [repo]
+// identifier = "shogochiai/TextDAO"
+// ref = "main"
+// bundles = ["textDAO"]
+// # local_path = "../TextDAO"  (use this checkout as is; nothing is cloned)
+// # submodule_cache = "/var/cache/mc-indexer/git"
+//
+// [solc]
+// path = "solc"
+// # cache_dir = "/root/.svm"  (pick the binary matching the pragma from here)
+//
+// [chains.mainnet]
+// chain_id = 1
+// rpc_url = "http://127.0.0.1:8545"
+// read_strategy = "storage_at"
+// contracts = { textDAO = "0x..." }
+//
+// [output]
+// path = "out.json"
+//
+// [limits]
+// max_steps = 16
+// max_array_length = 100000
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct IndexerConfig {
+    pub repo: RepoConfig,
+    #[serde(default)]
+    pub solc: SolcConfig,
+    #[serde(default)]
+    pub chains: HashMap<String, ChainConfig>, // key=chain name, e.g. mainnet
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default)]
+    pub limits: Limits,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RepoConfig {
+    pub identifier: String, // GitHub owner/name
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>, // branch, tag or commit; the default branch when None
+    pub bundles: Vec<String>,
+    #[serde(default = "RepoConfig::default_path")]
+    pub path: String, // checkout directory, relative to the working directory
+    pub local_path: Option<PathBuf>, // an existing checkout to use as is, instead of cloning identifier
+    pub submodule_cache: Option<PathBuf>, // bare mirrors of submodule remotes, reused across clones
+}
+
+impl RepoConfig {
+    fn default_path() -> String {
+        ".repo".to_string()
+    }
+}
+
+impl Default for RepoConfig {
+    fn default() -> Self {
+        Self {
+            identifier: String::new(),
+            git_ref: None,
+            bundles: Vec::new(),
+            path: Self::default_path(),
+            local_path: None,
+            submodule_cache: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SolcConfig {
+    #[serde(default = "SolcConfig::default_path")]
+    pub path: String, // used when there's no cache_dir
+    pub version: Option<String>, // constraint overriding foundry.toml and the pragma, e.g. "0.8.24" or "^0.8.20"
+    pub cache_dir: Option<PathBuf>, // installed binaries: solc-<version> or <version>/solc-<version>
+    pub artifacts: Option<PathBuf>, // forge out/ dir or build-info file, relative to the checkout; solc isn't run then
+    pub output_cache: Option<PathBuf>, // compiled outputs reused across runs; <repo.path>/.cache/solc when None
+}
+
+impl SolcConfig {
+    fn default_path() -> String {
+        "solc".to_string()
+    }
+}
+
+impl Default for SolcConfig {
+    fn default() -> Self {
+        Self { path: Self::default_path(), version: None, cache_dir: None, artifacts: None, output_cache: None }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    pub chain_id: i64,
+    pub rpc_url: String,
+    #[serde(default = "ChainConfig::default_read_strategy")]
+    pub read_strategy: String, // override or storage_at
+    pub contract_code: Option<String>, // only needed by the override strategy
+    #[serde(default)]
+    pub contracts: HashMap<String, String>, // key=bundle, value=contract address
+    pub block_number: Option<u64>,
+    pub block_hash: Option<String>,
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: DEFAULT_CHAIN_ID,
+            rpc_url: DEFAULT_RPC_URL.to_string(),
+            read_strategy: Self::default_read_strategy(),
+            contract_code: None,
+            contracts: HashMap::new(),
+            block_number: None,
+            block_hash: None,
+        }
+    }
+}
+
+impl ChainConfig {
+    fn default_read_strategy() -> String {
+        "override".to_string()
+    }
+
+    pub fn provider(&self, bundle: &str) -> Result<Provider, ExtractorError> {
+        let contract_addr = self.contracts.get(bundle)
+            .ok_or(ExtractorError::Config(format!("No contract address for bundle {} on chain {}.", bundle, self.chain_id)))?;
+        let block = match (self.block_number, &self.block_hash) {
+            (Some(number), _) => Some(BlockId::Number(number)),
+            (None, Some(hash)) => Some(BlockId::Hash(hash.clone())),
+            (None, None) => None,
+        };
+        Provider::new(
+            self.rpc_url.clone(),
+            self.chain_id,
+            contract_addr.clone(),
+            self.contract_code.clone(),
+            ReadStrategy::parse(&self.read_strategy)?,
+            block,
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    pub path: Option<PathBuf>, // stdout when None
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    #[serde(default = "Limits::default_max_steps")]
+    pub max_steps: usize, // BFS depth of a scan; nodes deeper than this are left out
+    #[serde(default = "Limits::default_max_array_length")]
+    pub max_array_length: usize, // longer dynamic arrays are skipped; their length word is taken as corrupt
+}
+
+impl Limits {
+    fn default_max_steps() -> usize {
+        16
+    }
+
+    fn default_max_array_length() -> usize {
+        100_000
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { max_steps: Self::default_max_steps(), max_array_length: Self::default_max_array_length() }
+    }
+}
+
+impl IndexerConfig {
+    pub fn load(path: &Path) -> Result<Self, ExtractorError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| ExtractorError::Config(format!("Failed to read {}: {}", path.display(), e)))?;
+        toml::from_str(&content).map_err(|e| ExtractorError::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    // Note: parse only; call validate() once CLI overrides are applied.
+    pub fn parse(content: &str) -> Result<Self, ExtractorError> {
+        toml::from_str(content).map_err(|e| ExtractorError::Config(e.to_string()))
+    }
+
+    pub fn validate(&self) -> Result<(), ExtractorError> {
+        let mut problems: Vec<String> = Vec::new();
+        match &self.repo.local_path {
+            Some(local_path) if !local_path.is_dir() => {
+                problems.push(format!("repo.local_path {} is not a directory", local_path.display()));
+            },
+            Some(_) if self.repo.git_ref.is_some() => {
+                problems.push("repo.ref can't pin repo.local_path; check the ref out there yourself".to_string());
+            },
+            Some(_) => {},
+            None => {
+                let parts: Vec<&str> = self.repo.identifier.split('/').collect();
+                if parts.len() != 2 || parts.iter().any(|part| part.is_empty()) {
+                    problems.push(format!("repo.identifier must look like owner/name, got {:?}", self.repo.identifier));
+                }
+            },
+        }
+        if self.repo.bundles.is_empty() {
+            problems.push("repo.bundles must name at least one bundle".to_string());
+        }
+        if self.limits.max_steps == 0 {
+            problems.push("limits.max_steps must be greater than 0".to_string());
+        }
+        if self.limits.max_array_length == 0 {
+            problems.push("limits.max_array_length must be greater than 0".to_string());
+        }
+
+        let mut names: Vec<&String> = self.chains.keys().collect();
+        names.sort();
+        for name in names {
+            let chain = &self.chains[name];
+            if chain.rpc_url.is_empty() {
+                problems.push(format!("chains.{}.rpc_url is empty", name));
+            }
+            if let Err(ExtractorError::Config(message)) = ReadStrategy::parse(&chain.read_strategy) {
+                problems.push(format!("chains.{}.read_strategy: {}", name, message));
+            }
+            if chain.block_number.is_some() && chain.block_hash.is_some() {
+                problems.push(format!("chains.{}: block_number and block_hash are exclusive", name));
+            }
+            let mut bundles: Vec<&String> = chain.contracts.keys().collect();
+            bundles.sort();
+            for bundle in bundles {
+                if !self.repo.bundles.contains(bundle) {
+                    problems.push(format!("chains.{}.contracts.{}: {} is not in repo.bundles", name, bundle, bundle));
+                }
+                let addr = &chain.contracts[bundle];
+                let is_address = addr.len() == 42 && addr.starts_with("0x") && addr[2..].chars().all(|c| c.is_ascii_hexdigit());
+                if !is_address {
+                    problems.push(format!("chains.{}.contracts.{}: {:?} is not a 0x-prefixed 20-byte address", name, bundle, addr));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ExtractorError::Config(format!("invalid config:\n  - {}", problems.join("\n  - "))))
+        }
+    }
+
+    // The requested bundle, or the only one configured.
+    pub fn bundle(&self, requested: Option<&str>) -> Result<String, ExtractorError> {
+        match requested {
+            Some(bundle) if self.repo.bundles.iter().any(|b| b == bundle) => Ok(bundle.to_string()),
+            Some(bundle) => Err(ExtractorError::Config(format!("Bundle {} is not in repo.bundles {:?}.", bundle, self.repo.bundles))),
+            None if self.repo.bundles.len() == 1 => Ok(self.repo.bundles[0].clone()),
+            None => Err(ExtractorError::Config(format!("Several bundles are configured {:?}; pick one.", self.repo.bundles))),
+        }
+    }
+
+    // The requested chain, or the only one configured.
+    pub fn chain(&self, requested: Option<&str>) -> Result<(String, ChainConfig), ExtractorError> {
+        match requested {
+            Some(name) => self.chains.get(name).map(|chain| (name.to_string(), chain.clone()))
+                .ok_or(ExtractorError::Config(format!("No [chains.{}] in the config.", name))),
+            None if self.chains.len() == 1 => Ok(self.chains.iter().next().map(|(name, chain)| (name.clone(), chain.clone())).unwrap()),
+            None if self.chains.is_empty() => Err(ExtractorError::Config("No [chains.<name>] in the config.".to_string())),
+            None => Err(ExtractorError::Config("Several chains are configured; pick one.".to_string())),
+        }
+    }
+
+    pub fn fetcher(&self, bundle: &str, base_path: PathBuf) -> Result<MCRepoFetcher, ExtractorError> {
+        match &self.repo.local_path {
+            Some(local_path) => MCRepoFetcher::local(local_path.clone(), bundle.to_string(), base_path, &self.repo.path),
+            None => Ok(MCRepoFetcher::with_repo_path(self.repo.identifier.clone(), bundle.to_string(), base_path, &self.repo.path)?
+                .with_ref(self.repo.git_ref.clone())
+                .with_submodule_cache(self.repo.submodule_cache.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+[repo]
+identifier = "shogochiai/TextDAO"
+ref = "main"
+bundles = ["textDAO"]
+
+[chains.mainnet]
+chain_id = 1
+rpc_url = "http://127.0.0.1:8545"
+read_strategy = "storage_at"
+contracts = { textDAO = "0x5FbDB2315678afecb367f032d93F642f64180aa3" }
+"#;
+
+    #[test]
+    fn test_parse_with_defaults() {
+        let config = IndexerConfig::parse(SAMPLE).unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.repo.git_ref, Some("main".to_string()));
+        assert_eq!(config.repo.path, ".repo");
+        assert_eq!(config.solc, SolcConfig::default());
+        assert_eq!(config.limits.max_steps, 16);
+        assert_eq!(config.limits.max_array_length, 100_000);
+        assert_eq!(config.output.path, None);
+        assert_eq!(config.bundle(None).unwrap(), "textDAO");
+        assert_eq!(config.chain(None).unwrap().1.read_strategy, "storage_at");
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let mut config = IndexerConfig::parse(SAMPLE).unwrap();
+        config.repo.identifier = "TextDAO".to_string();
+        let chain = config.chains.get_mut("mainnet").unwrap();
+        chain.read_strategy = "trace".to_string();
+        chain.contracts.insert("other".to_string(), "0x12".to_string());
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("repo.identifier must look like owner/name"));
+        assert!(message.contains("chains.mainnet.read_strategy"));
+        assert!(message.contains("chains.mainnet.contracts.other: other is not in repo.bundles"));
+        assert!(message.contains("\"0x12\" is not a 0x-prefixed 20-byte address"));
+
+        let unknown = IndexerConfig::parse("[repo]\nidentifier = \"a/b\"\nbundles = []\nbranch = \"main\"\n").unwrap_err();
+        assert!(unknown.to_string().contains("unknown field `branch`"));
+    }
+}
@@ -0,0 +1,61 @@
+// mc-companion embedded indexer: reads a meta contract bundle's storage into a tree of values.
+//
+// config:  IndexerConfig::load("indexer.toml".as_ref())?, then validate()
+// fetch:   config.fetcher(bundle, base_path)?.fetch()
+// compile: StorageLayout::compile(&fetcher, &config.solc)
+// scan:    scan(&layout, fetcher.load_perf_config()?, from_provider(provider), &config).await
+// output:  extractor.output_tree() / OutputTree::document(extractor.registry())
+
+mod compiler;
+mod extractor;
+mod executor;
+mod registry;
+mod executable;
+mod config_util;
+mod type_kind;
+mod type_id;
+mod eth_call;
+mod ast_node;
+mod ast_layout;
+mod context;
+mod mc_repo_fetcher;
+mod provider;
+mod block_id;
+mod slot_reader;
+mod slot_util;
+mod value_decoder;
+mod error;
+mod storage_layout;
+mod output_tree;
+mod plan;
+mod config_lint;
+mod server;
+mod indexer_config;
+mod solc_version;
+#[cfg(test)]
+mod test_support;
+
+pub use block_id::BlockId;
+pub use config_lint::{ConfigLint, LintIssue, LintLevel};
+pub use error::ExtractorError;
+pub use extractor::Extractor;
+pub use indexer_config::{ChainConfig, IndexerConfig, Limits, OutputConfig, RepoConfig, SolcConfig, DEFAULT_CONFIG_PATH};
+pub use mc_repo_fetcher::{MCRepoFetcher, RepoSource};
+pub use output_tree::OutputTree;
+pub use plan::{PlanEntry, PlannedSlot, Planner};
+pub use provider::{Provider, ReadStrategy};
+pub use registry::{Constraint, Registry};
+pub use server::Server;
+pub use slot_reader::{from_provider, MemoryReader, SlotReader};
+pub use storage_layout::StorageLayout;
+pub use value_decoder::TypedValue;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Scan every schema struct of `layout` through `reader`, at the single block the reader resolves.
+pub async fn scan(layout: &StorageLayout, constraints: HashMap<usize, Constraint>, reader: Rc<dyn SlotReader>, config: &IndexerConfig) -> Result<Extractor, ExtractorError> {
+    let mut extractor = Extractor::from_layout(layout, constraints, reader, config)?;
+    extractor.listen().await?;
+    Ok(extractor)
+}
@@ -1,69 +1,311 @@
-#![allow(unused_imports)]
-
-mod compiler;
-mod extractor;
-mod executor;
-mod registry;
-mod executable;
-mod config_util;
-mod type_kind;
-mod eth_call;
-mod perf_expression_evaluator;
-mod ast_node;
-mod context;
-mod mc_repo_fetcher;
-
 extern crate dotenv;
 
-use compiler::Compiler;
-use extractor::Extractor;
-use executor::Executor;
-use registry::Registry;
-use executable::Executable;
-use config_util::ConfigUtil;
-use type_kind::TypeKind;
-use eth_call::EthCall;
-use perf_expression_evaluator::PerfExpressionEvaluator;
-use ast_node::ASTNode;
-use context::Context;
-use mc_repo_fetcher::MCRepoFetcher;
+use mc_indexer_extractor::{from_provider, ChainConfig, ConfigLint, ExtractorError, IndexerConfig, LintLevel, MCRepoFetcher, OutputTree, Planner, Server, StorageLayout, DEFAULT_CONFIG_PATH};
 
-use std::collections::HashMap;
-use std::process::Command;
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
 use serde_json::Value;
-use std::cell::RefCell;
-use std::env;
+use std::path::PathBuf;
 
-#[tokio::main]
-async fn main() {
-    dotenv::dotenv().ok();
-    let project_root = env::current_dir().unwrap();
-    let identifier = env::var("REPO_IDENTIFIER").unwrap();
-    let bundle = env::var("BUNDLE_NAME").unwrap();
+// Settings start from indexer.toml; flags (or the env vars of .env.sample) override it.
+#[derive(Parser)]
+#[command(name = "mc-indexer-extractor", about = "Read a meta contract bundle's storage into a tree of values")]
+struct Cli {
+    /// Project config; optional when flags/env vars cover everything
+    #[arg(long, global = true, env = "INDEXER_CONFIG", default_value = DEFAULT_CONFIG_PATH)]
+    config: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Clone the repository, or update it when it is already there (a --local checkout is only read)
+    Fetch {
+        #[command(flatten)]
+        repo: RepoArgs,
+    },
+    /// Compile the bundle's storage layout
+    Layout {
+        #[command(flatten)]
+        repo: RepoArgs,
+        /// Write the layout here instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Print the slots a scan would visit, without any RPC
+    Plan {
+        #[command(flatten)]
+        repo: RepoArgs,
+    },
+    /// Scan the contract and print the output document
+    Extract {
+        #[command(flatten)]
+        repo: RepoArgs,
+        #[command(flatten)]
+        chain: ChainArgs,
+        /// Write the document here instead of output.path (or stdout)
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Check Indexer.yaml against the layout
+    Validate {
+        #[command(flatten)]
+        repo: RepoArgs,
+    },
+    /// Serve a document written by `extract --out` over HTTP
+    Serve {
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+    },
+}
+
+#[derive(Args)]
+struct RepoArgs {
+    /// GitHub repository, e.g. shogochiai/TextDAO
+    #[arg(long = "repo", env = "REPO_IDENTIFIER")]
+    identifier: Option<String>,
+    /// Branch, tag or commit that `fetch` checks out (the default branch otherwise)
+    #[arg(long = "ref", env = "REPO_REF")]
+    git_ref: Option<String>,
+    /// Directory of bare mirrors for submodules (lib/mc, lib/forge-std, ...)
+    #[arg(long, env = "SUBMODULE_CACHE")]
+    submodule_cache: Option<PathBuf>,
+    /// Existing checkout to use as is; never cloned, reset or written to
+    #[arg(long = "local", env = "LOCAL_REPO_PATH")]
+    local_path: Option<PathBuf>,
+    /// One of repo.bundles; required when several are configured
+    #[arg(long, env = "BUNDLE_NAME")]
+    bundle: Option<String>,
+    /// Directory holding the repo path (defaults to the current directory)
+    #[arg(long)]
+    base_path: Option<PathBuf>,
+    #[arg(long, env = "REPO_PATH")]
+    repo_path: Option<String>,
+    #[arg(long = "solc", env = "SOLC_PATH")]
+    solc_path: Option<String>,
+    /// Directory of installed solc binaries to pick the pragma's version from
+    #[arg(long, env = "SOLC_CACHE")]
+    solc_cache: Option<PathBuf>,
+    /// Version constraint overriding foundry.toml and the pragma
+    #[arg(long, env = "SOLC_VERSION")]
+    solc_version: Option<String>,
+    /// Where compiled solc outputs are kept between runs (defaults to <repo path>/.cache/solc)
+    #[arg(long, env = "SOLC_OUTPUT_CACHE")]
+    solc_output_cache: Option<PathBuf>,
+    /// forge out/ dir or build-info file (relative to the checkout) to read instead of running solc
+    #[arg(long, env = "FORGE_ARTIFACTS")]
+    artifacts: Option<PathBuf>,
+}
 
-    let mc_repo_fetcher = MCRepoFetcher::new(identifier.clone(), bundle.clone(), Some(project_root.clone()));
-    // mc_repo_fetcher.clone_repo().unwrap();
-    
+impl RepoArgs {
+    fn apply(&self, config: &mut IndexerConfig) {
+        if let Some(identifier) = &self.identifier {
+            config.repo.identifier = identifier.clone();
+        }
+        if let Some(git_ref) = &self.git_ref {
+            config.repo.git_ref = Some(git_ref.clone());
+        }
+        if let Some(submodule_cache) = &self.submodule_cache {
+            config.repo.submodule_cache = Some(submodule_cache.clone());
+        }
+        if let Some(local_path) = &self.local_path {
+            config.repo.local_path = Some(local_path.clone());
+        }
+        if let Some(bundle) = &self.bundle {
+            if !config.repo.bundles.contains(bundle) {
+                config.repo.bundles.push(bundle.clone());
+            }
+        }
+        if let Some(repo_path) = &self.repo_path {
+            config.repo.path = repo_path.clone();
+        }
+        if let Some(solc_path) = &self.solc_path {
+            config.solc.path = solc_path.clone();
+        }
+        if let Some(solc_cache) = &self.solc_cache {
+            config.solc.cache_dir = Some(solc_cache.clone());
+        }
+        if let Some(solc_version) = &self.solc_version {
+            config.solc.version = Some(solc_version.clone());
+        }
+        if let Some(solc_output_cache) = &self.solc_output_cache {
+            config.solc.output_cache = Some(solc_output_cache.clone());
+        }
+        if let Some(artifacts) = &self.artifacts {
+            config.solc.artifacts = Some(artifacts.clone());
+        }
+    }
 
-    let mut compiler = Compiler::new("solc".to_string(), project_root.clone(), identifier.clone(), bundle.clone());
-    let base_slots = compiler.prepare_base_slots().unwrap();
+    fn fetcher(&self, config: &IndexerConfig) -> Result<MCRepoFetcher, ExtractorError> {
+        let base_path = match &self.base_path {
+            Some(base_path) => base_path.clone(),
+            None => std::env::current_dir().map_err(|e| ExtractorError::Config(format!("No current directory: {}", e)))?,
+        };
+        config.fetcher(&config.bundle(self.bundle.as_deref())?, base_path)
+    }
 
-    let base_slots_vec: Vec<String> = base_slots.keys().cloned().collect();
-    mc_repo_fetcher.gen_dummy_contract(&base_slots_vec).unwrap();
-    mc_repo_fetcher.gen_standard_json_input().unwrap();
-    let storage_layout_blob = compiler.prepare_storage_layout().unwrap();
+    // Note: only `fetch` clones or moves the checkout; the other stages read the tree as it is.
+    fn fetched(&self, config: &IndexerConfig) -> Result<MCRepoFetcher, ExtractorError> {
+        let mut fetcher = self.fetcher(config)?;
+        if !fetcher.is_fetched() {
+            return Err(ExtractorError::Fetch(format!("{} isn't at {}. Run fetch first.", fetcher.identifier, fetcher.identifier_path.display())));
+        }
+        fetcher.load_docs()?;
+        Ok(fetcher)
+    }
+}
 
-    #[allow(unused_mut)]
-    let mut context = Context {
-        registry: Registry::new(
-                    storage_layout_blob,
-                    mc_repo_fetcher.load_perf_config().expect("config load failed"),
-                    bundle.clone()
-                ),
+#[derive(Args)]
+struct ChainArgs {
+    /// One of the [chains.<name>] tables; required when several are configured
+    #[arg(long)]
+    chain: Option<String>,
+    #[arg(long, env = "RPC_URL")]
+    rpc_url: Option<String>,
+    #[arg(long, env = "CHAIN_ID")]
+    chain_id: Option<i64>,
+    #[arg(long, env = "CONTRACT_ADDR")]
+    contract_addr: Option<String>,
+    #[arg(long, env = "CONTRACT_CODE")]
+    contract_code: Option<String>,
+    /// override or storage_at
+    #[arg(long, env = "READ_STRATEGY")]
+    read_strategy: Option<String>,
+    #[arg(long, env = "BLOCK_NUMBER", conflicts_with = "block_hash")]
+    block_number: Option<u64>,
+    #[arg(long, env = "BLOCK_HASH")]
+    block_hash: Option<String>,
+}
+
+impl ChainArgs {
+    // Overrides the selected chain and returns its name.
+    // Note: a chain is only made up when the config has none and the CLI gives at least --rpc-url and --contract-addr
+    fn apply(&self, config: &mut IndexerConfig, bundle: &str) -> Result<String, ExtractorError> {
+        let name = match &self.chain {
+            Some(name) if config.chains.contains_key(name) => name.clone(),
+            Some(name) => return Err(ExtractorError::Config(format!("No [chains.{}] in the config.", name))),
+            None if config.chains.len() == 1 => config.chains.keys().next().cloned().unwrap_or_default(),
+            None if config.chains.is_empty() && self.rpc_url.is_some() && self.contract_addr.is_some() => {
+                config.chains.insert("default".to_string(), ChainConfig::default());
+                "default".to_string()
+            },
+            None if config.chains.is_empty() => return Err(ExtractorError::Config("No [chains.<name>] in the config; add one or pass --rpc-url and --contract-addr.".to_string())),
+            None => return Err(ExtractorError::Config("Several chains are configured; pick one with --chain.".to_string())),
+        };
+        let chain = config.chains.get_mut(&name).ok_or(ExtractorError::Config(format!("No [chains.{}] in the config.", name)))?;
+        if let Some(rpc_url) = &self.rpc_url {
+            chain.rpc_url = rpc_url.clone();
+        }
+        if let Some(chain_id) = self.chain_id {
+            chain.chain_id = chain_id;
+        }
+        if let Some(contract_addr) = &self.contract_addr {
+            chain.contracts.insert(bundle.to_string(), contract_addr.clone());
+        }
+        if let Some(contract_code) = &self.contract_code {
+            chain.contract_code = Some(contract_code.clone());
+        }
+        if let Some(read_strategy) = &self.read_strategy {
+            chain.read_strategy = read_strategy.clone();
+        }
+        if self.block_number.is_some() || self.block_hash.is_some() {
+            chain.block_number = self.block_number;
+            chain.block_hash = self.block_hash.clone();
+        }
+        Ok(name)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    if let Err(err) = run(cli).await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), ExtractorError> {
+    let mut config = if cli.config.exists() {
+        IndexerConfig::load(&cli.config)?
+    } else {
+        IndexerConfig::default()
     };
 
-    let mut extractor = Extractor::new(context);
-    extractor.init_members_from_compiler(&base_slots);
+    match cli.command {
+        Command::Fetch { repo } => {
+            repo.apply(&mut config);
+            config.validate()?;
+            let mut fetcher = repo.fetcher(&config)?;
+            fetcher.fetch()?;
+            eprintln!("Fetched {} into {} at {}", fetcher.identifier, fetcher.identifier_path.display(), fetcher.head_commit().unwrap_or_else(|| "no commit".to_string()));
+        },
+        Command::Layout { repo, out } => {
+            repo.apply(&mut config);
+            config.validate()?;
+            let layout = StorageLayout::compile(&repo.fetched(&config)?, &config.solc)?;
+            write_json(&json!({ "bundle": layout.bundle, "base_slots": layout.base_slots, "storageLayout": layout.storage_layout() }), out)?;
+        },
+        Command::Plan { repo } => {
+            repo.apply(&mut config);
+            config.validate()?;
+            let layout = StorageLayout::compile(&repo.fetched(&config)?, &config.solc)?;
+            for entry in Planner::plan(&layout, config.limits.max_steps)? {
+                println!("{}{} ({}) slot={} offset={}", "  ".repeat(entry.depth), entry.path, entry.label, entry.slot, entry.offset);
+            }
+        },
+        Command::Extract { repo, chain, out } => {
+            repo.apply(&mut config);
+            let bundle = config.bundle(repo.bundle.as_deref())?;
+            let chain_name = chain.apply(&mut config, &bundle)?;
+            if let Some(out) = out {
+                config.output.path = Some(out);
+            }
+            config.validate()?;
+
+            let provider = config.chains[&chain_name].provider(&bundle)?;
+            let fetcher = repo.fetched(&config)?;
+            let layout = StorageLayout::compile(&fetcher, &config.solc)?;
+            let extractor = mc_indexer_extractor::scan(&layout, fetcher.load_perf_config()?, from_provider(provider), &config).await?;
+            write_json(&OutputTree::document(extractor.registry()), config.output.path.clone())?;
+        },
+        Command::Validate { repo } => {
+            repo.apply(&mut config);
+            config.validate()?;
+            let fetcher = repo.fetched(&config)?;
+            let layout = StorageLayout::compile(&fetcher, &config.solc)?;
+            let issues = ConfigLint::lint(&fetcher, Some(&layout), config.limits.max_steps)?;
+            for issue in issues.iter() {
+                println!("{}", issue);
+            }
+            let errors = issues.iter().filter(|issue| issue.level == LintLevel::Error).count();
+            if errors > 0 {
+                return Err(ExtractorError::Config(format!("Indexer.yaml has {} error(s).", errors)));
+            }
+            println!("Indexer.yaml is valid.");
+        },
+        Command::Serve { input, host, port } => {
+            let content = std::fs::read_to_string(&input).map_err(|e| ExtractorError::Config(format!("Failed to read {}: {}", input.display(), e)))?;
+            let document: Value = serde_json::from_str(&content).map_err(|e| ExtractorError::Config(format!("{} is not JSON: {}", input.display(), e)))?;
+            Server::serve(document, &format!("{}:{}", host, port)).await?;
+        },
+    }
+    Ok(())
+}
 
-    extractor.listen().await;
-}
\ No newline at end of file
+fn write_json(value: &Value, out: Option<PathBuf>) -> Result<(), ExtractorError> {
+    let content = serde_json::to_string_pretty(value).map_err(|e| ExtractorError::Config(format!("Failed to serialize the output: {}", e)))?;
+    match out {
+        Some(out) => std::fs::write(&out, content).map_err(|e| ExtractorError::Config(format!("Failed to write {}: {}", out.display(), e))),
+        None => {
+            println!("{}", content);
+            Ok(())
+        },
+    }
+}
@@ -1,28 +1,34 @@
 use crate::registry::Constraint;
 
-use super::executor::Executor;
-use super::registry::Registry;
-use super::ast_node::ASTNode;
 use super::config_util::ConfigUtil;
-use super::executable::Executable;
+use super::error::ExtractorError;
 
 
 use std::collections::HashMap;
-use serde_json::Value;
+use serde_json::json;
 use yaml_rust::Yaml;
 use yaml_rust::YamlLoader;
-use regex::Regex;
 use git2::Repository;
 use std::fs;
-use std::error::Error;
-use std::env;
 use std::path::PathBuf;
 use std::path::Path;
 
 
+// Under repo.path (RepoConfig::path), next to the checkouts.
+const STANDARD_JSON_INPUT_NAME: &str = "standard_json_input_layout.json";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RepoSource {
+    GitHub, // cloned into local_repo_path/identifier and owned by the fetcher
+    Local,  // an existing checkout on disk; never cloned, reset, deleted or written to
+}
+
 #[allow(unused)]
 pub struct MCRepoFetcher {
+    pub source: RepoSource,
     pub url: String,
+    pub git_ref: Option<String>, // branch, tag or commit to check out; the remote's default branch when None
+    pub submodule_cache: Option<PathBuf>, // bare mirrors of submodule remotes, shared across checkouts
     pub base_path: PathBuf,
     pub identifier: String,
     pub bundle: String,
@@ -30,128 +36,307 @@ pub struct MCRepoFetcher {
     pub identifier_path: PathBuf,
     pub perf_config_path: PathBuf,
     pub schema_path: PathBuf,
-    pub dummy_path: PathBuf,
     pub docs: Vec<Yaml>,
-    pub standard_json_input_layout_sample_path: PathBuf,
     pub standard_json_input_layout_path: PathBuf,
+    pub standard_json_input_path: PathBuf, // what gen_standard_json_input writes and solc reads
 }
 
 impl MCRepoFetcher {
-    pub fn new(identifier: String, bundle: String, base_path: Option<PathBuf>) -> Self {
-        let _base_path = base_path.unwrap_or_else(|| env::current_dir().unwrap() );
-        let local_repo_path = _base_path.join(format!("{}", env::var("REPO_PATH").unwrap()));
-        let identifier_path = local_repo_path.join(format!("{}", identifier));
+    // Note: Only resolves paths and reads Indexer.yaml when it's already there. Cloning is up to fetch().
+    pub fn with_repo_path(identifier: String, bundle: String, base_path: PathBuf, repo_path: &str) -> Result<Self, ExtractorError> {
+        let identifier_path = base_path.join(repo_path).join(&identifier);
+        Self::build(RepoSource::GitHub, identifier, bundle, base_path, repo_path, identifier_path)
+    }
+
+    // A checkout we develop in. The generated solc input goes to a scratch dir under repo_path.
+    pub fn local(checkout: PathBuf, bundle: String, base_path: PathBuf, repo_path: &str) -> Result<Self, ExtractorError> {
+        if !checkout.join("src").join(&bundle).exists() {
+            return Err(ExtractorError::Fetch(format!("{} has no src/{}; is it a meta contract checkout?", checkout.display(), bundle)));
+        }
+        let identifier = checkout.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| "local".to_string());
+        Self::build(RepoSource::Local, identifier, bundle, base_path, repo_path, checkout)
+    }
+
+    fn build(source: RepoSource, identifier: String, bundle: String, base_path: PathBuf, repo_path: &str, identifier_path: PathBuf) -> Result<Self, ExtractorError> {
+        let local_repo_path = base_path.join(repo_path);
         let storage_path = identifier_path.join(format!("src/{}/storages", bundle));
-        let schema_path = storage_path.join(format!("Schema.sol"));
-        let dummy_path = storage_path.join(format!("Dummy.sol"));
-        let perf_config_path = storage_path.join(format!("Indexer.yaml"));
-        let standard_json_input_layout_sample_path = local_repo_path.join(format!("{}", env::var("STANDARD_JSON_INPUT_LAYOUT_SAMPLE_NAME").unwrap_or_else(|_| "standard_json_input_layout_sample.json".to_string())));
-        let standard_json_input_layout_path = local_repo_path.join(format!("{}", env::var("STANDARD_JSON_INPUT_LAYOUT_NAME").unwrap_or_else(|_| "standard_json_input_layout.json".to_string())));
+        let schema_path = storage_path.join("Schema.sol");
+        let perf_config_path = storage_path.join("Indexer.yaml");
+        let standard_json_input_layout_path = local_repo_path.join(STANDARD_JSON_INPUT_NAME);
+        let standard_json_input_path = match source {
+            RepoSource::GitHub => standard_json_input_layout_path.clone(),
+            RepoSource::Local => local_repo_path.join(".scratch").join(&identifier).join(&bundle).join(STANDARD_JSON_INPUT_NAME),
+        };
 
-        let _self = Self {
+        let mut _self = Self {
+            source,
+            git_ref: None,
+            submodule_cache: None,
             url: format!("https://github.com/{}.git", identifier.clone()),
-            base_path: _base_path.clone(),
+            base_path: base_path.clone(),
             identifier: identifier.clone(),
             bundle: bundle.clone(),
             local_repo_path: local_repo_path.clone(),
             identifier_path: identifier_path.clone(),
             schema_path: schema_path.clone(),
-            dummy_path: dummy_path.clone(),
             perf_config_path: perf_config_path.clone(),
             docs: Vec::new(),
-            standard_json_input_layout_sample_path: standard_json_input_layout_sample_path.clone(),
             standard_json_input_layout_path: standard_json_input_layout_path.clone(),
+            standard_json_input_path,
         };
+        if _self.perf_config_path.exists() {
+            _self.load_docs()?;
+        }
+        Ok(_self)
+    }
 
-        _self.clone_repo().unwrap();
+    pub fn with_ref(mut self, git_ref: Option<String>) -> Self {
+        self.git_ref = git_ref;
+        self
+    }
 
-        let yaml_str = fs::read_to_string(&perf_config_path.clone()).expect("Failed to read YAML file");
-        let docs = YamlLoader::load_from_str(&yaml_str).expect("Failed to parse YAML");
+    pub fn with_submodule_cache(mut self, submodule_cache: Option<PathBuf>) -> Self {
+        self.submodule_cache = submodule_cache;
+        self
+    }
 
+    pub fn load_docs(&mut self) -> Result<(), ExtractorError> {
+        let yaml_str = fs::read_to_string(&self.perf_config_path).map_err(|e| ExtractorError::Config(format!("Failed to read {}: {}", self.perf_config_path.display(), e)))?;
+        self.docs = YamlLoader::load_from_str(&yaml_str).map_err(|e| ExtractorError::Config(format!("Failed to parse {}: {}", self.perf_config_path.display(), e)))?;
+        Ok(())
+    }
 
-        // Note: I wanted to prepare docs instance after cloning. I fix it lator.
-        let _self2 = Self {
-            url: format!("https://github.com/{}.git", identifier.clone()),
-            base_path: _base_path.clone(),
-            identifier: identifier.clone(),
-            bundle: bundle.clone(),
-            local_repo_path: local_repo_path.clone(),
-            identifier_path: identifier_path.clone(),
-            schema_path: schema_path.clone(),
-            dummy_path: dummy_path.clone(),
-            perf_config_path: perf_config_path.clone(),
-            docs,
-            standard_json_input_layout_sample_path: standard_json_input_layout_sample_path.clone(),
-            standard_json_input_layout_path: standard_json_input_layout_path.clone(),
-        };
-        _self2
+    pub fn is_fetched(&self) -> bool {
+        match self.source {
+            RepoSource::GitHub => self.identifier_path.join(".git").exists(),
+            RepoSource::Local => self.identifier_path.exists(),
+        }
     }
 
-    pub fn clone_repo(&self) -> std::result::Result<(), Box<dyn std::error::Error>> {
-        // Check if the target directory already exists
-        if self.local_repo_path.exists() && self.identifier_path.join("lib/mc").exists() {
-            if let Err(err) = std::fs::remove_dir_all(&self.identifier_path) {
-                panic!("Failed to remove directory: {}", err);
+    // Clone on first use, otherwise fetch incrementally; then check out git_ref.
+    // A local checkout is only (re)read.
+    pub fn fetch(&mut self) -> Result<(), ExtractorError> {
+        if self.source == RepoSource::Local {
+            // Note: nothing to fetch
+        } else {
+            if self.is_fetched() {
+                self.update_repo()?;
+            } else {
+                self.clone_repo()?;
             }
+            self.checkout()?;
         }
+        self.load_docs()
+    }
 
-        // Clone the repository
-        let repo = Repository::clone(&self.url, &self.identifier_path)?;
-        println!("Cloned repository: {}", repo.path().display());
+    // Fetch new branches and tags from origin. The worktree stays where it is until checkout().
+    pub fn update_repo(&self) -> Result<(), ExtractorError> {
+        self.ensure_owned("update")?;
+        let fetch_error = |e: git2::Error| ExtractorError::Fetch(format!("Failed to update {}: {}", self.identifier_path.display(), e));
+
+        let repo = Repository::open(&self.identifier_path).map_err(fetch_error)?;
+        let mut remote = repo.find_remote("origin").map_err(fetch_error)?;
+        remote.fetch(&["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"], None, None).map_err(fetch_error)?;
+        eprintln!("Updated repository: {}", repo.path().display());
         Ok(())
     }
 
-    pub fn gen_dummy_contract(&self, base_slots: &Vec<String>) -> Result<(), Box<dyn Error>> {
-        let mut dummy_contract = String::new();
-        dummy_contract.push_str("// SPDX-License-Identifier: MIT\n");
-        dummy_contract.push_str("pragma solidity ^0.8.24;\n\n");
-        dummy_contract.push_str("import { Schema } from \"bundle/");
-        dummy_contract.push_str(&self.bundle);
-        dummy_contract.push_str("/storages/Schema.sol\";\n\n");
-        dummy_contract.push_str("contract Dummy {\n");
-
-        for slot in base_slots {
-            dummy_contract.push_str("    Schema.");
-            dummy_contract.push_str(slot);
-            dummy_contract.push_str(" $");
-            dummy_contract.push_str(&slot.to_lowercase());
-            dummy_contract.push_str(";\n");
+    // Detach HEAD at git_ref and bring submodules to the commits it pins. Returns the commit hash.
+    // Note: the superproject only uses what is already fetched; submodules fetch what they miss.
+    pub fn checkout(&self) -> Result<String, ExtractorError> {
+        self.ensure_owned("check out a ref in")?;
+        let checkout_error = |e: git2::Error| ExtractorError::Fetch(format!("Failed to check out {} in {}: {}", self.git_ref.as_deref().unwrap_or("the default branch"), self.identifier_path.display(), e));
+
+        let mut repo = Repository::open(&self.identifier_path).map_err(checkout_error)?;
+        let commit_id = {
+            let commit = Self::resolve_ref(&repo, self.git_ref.as_deref()).map_err(checkout_error)?;
+            repo.set_head_detached(commit.id()).map_err(checkout_error)?;
+            repo.reset(commit.as_object(), git2::ResetType::Hard, None).map_err(checkout_error)?;
+            commit.id()
+        };
+        self.update_submodules(&mut repo).map_err(|e| ExtractorError::Fetch(format!("Failed to update submodules of {}: {}", self.identifier_path.display(), e)))?;
+        eprintln!("Checked out {} at {}", self.git_ref.as_deref().unwrap_or("origin/HEAD"), commit_id);
+        Ok(commit_id.to_string())
+    }
+
+    // Init and update every submodule, recursively, to the commit recorded in its parent (lib/mc, lib/forge-std, ...).
+    fn update_submodules(&self, repo: &mut Repository) -> Result<(), git2::Error> {
+        let names: Vec<String> = repo.submodules()?.iter().filter_map(|submodule| submodule.name().map(|name| name.to_string())).collect();
+        for name in names {
+            let (url, pinned) = {
+                let mut submodule = repo.find_submodule(&name)?;
+                submodule.init(false)?;
+                (submodule.url().map(|url| url.to_string()), submodule.index_id())
+            };
+            // Note: the mirror only replaces the url in .git/config; .gitmodules stays as committed
+            if let (Some(cache), Some(url)) = (&self.submodule_cache, url) {
+                if let Some(mirror) = Self::mirror(cache, &url, pinned)? {
+                    repo.submodule_set_url(&name, &mirror.display().to_string())?;
+                }
+            }
+            let mut submodule = repo.find_submodule(&name)?;
+            submodule.sync()?;
+            submodule.update(false, None)?;
+            let mut sub_repo = submodule.open()?;
+            self.update_submodules(&mut sub_repo)?;
         }
+        Ok(())
+    }
 
-        dummy_contract.push_str("}\n");
+    // Bare mirror of url under cache: cloned once, fetched only when the pinned commit is missing.
+    // Relative submodule urls resolve against the parent's remote, so they bypass the cache.
+    fn mirror(cache: &Path, url: &str, pinned: Option<git2::Oid>) -> Result<Option<PathBuf>, git2::Error> {
+        if url.starts_with("./") || url.starts_with("../") {
+            return Ok(None);
+        }
+        let key = url.rsplit("://").next().unwrap_or(url).replace(':', "/");
+        let path = cache.join(format!("{}.git", key.trim_start_matches('/').trim_end_matches('/').trim_end_matches(".git")));
+        if !path.exists() {
+            git2::build::RepoBuilder::new().bare(true).clone(url, &path)?;
+        } else {
+            let mirror = Repository::open_bare(&path)?;
+            let has_pinned = pinned.map(|oid| mirror.find_commit(oid).is_ok()).unwrap_or(false);
+            if !has_pinned {
+                mirror.find_remote("origin")?.fetch(&["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"], None, None)?;
+            }
+        }
+        Ok(Some(path))
+    }
 
-        std::fs::write(&self.dummy_path, dummy_contract)?;
+    // Note: a branch name means the remote branch, so an update moves it; tags and hashes stay put.
+    fn resolve_ref<'r>(repo: &'r Repository, git_ref: Option<&str>) -> Result<git2::Commit<'r>, git2::Error> {
+        let git_ref = match git_ref {
+            Some(git_ref) => git_ref,
+            None => {
+                return repo.find_reference("refs/remotes/origin/HEAD")
+                    .or_else(|_| repo.find_reference("FETCH_HEAD"))
+                    .and_then(|reference| reference.peel_to_commit());
+            },
+        };
+        for name in [format!("refs/remotes/origin/{}", git_ref), format!("refs/tags/{}", git_ref)] {
+            if let Ok(reference) = repo.find_reference(&name) {
+                return reference.peel_to_commit();
+            }
+        }
+        repo.revparse_single(git_ref).and_then(|object| object.peel_to_commit())
+    }
+
+    // The commit the sources are read from; None for a local checkout outside git.
+    pub fn head_commit(&self) -> Option<String> {
+        let repo = match self.source {
+            RepoSource::GitHub => Repository::open(&self.identifier_path).ok()?,
+            RepoSource::Local => Repository::discover(&self.identifier_path).ok()?,
+        };
+        let commit = repo.head().ok()?.peel_to_commit().ok()?;
+        Some(commit.id().to_string())
+    }
+
+    // Clone url into identifier_path, or fetch into the clone already there. Nothing on disk is ever removed.
+    pub fn clone_repo(&self) -> Result<(), ExtractorError> {
+        self.ensure_owned("clone over")?;
+        if self.is_fetched() {
+            return self.update_repo();
+        }
+        let occupied = fs::read_dir(&self.identifier_path).map(|mut entries| entries.next().is_some()).unwrap_or(false);
+        if occupied {
+            return Err(ExtractorError::Fetch(format!("{} exists but isn't a git clone. Move it away, or pass it with --local to use it as is.", self.identifier_path.display())));
+        }
+
+        let repo = Repository::clone(&self.url, &self.identifier_path).map_err(|e| ExtractorError::Fetch(format!("Failed to clone {}: {}", self.url, e)))?;
+        eprintln!("Cloned repository: {}", repo.path().display());
         Ok(())
     }
 
-    pub fn gen_standard_json_input(&self) -> Result<(), Box<dyn Error>> {
-        if self.standard_json_input_layout_path.exists() {
-            match fs::read_to_string(&self.standard_json_input_layout_path) {
-                Ok(content) => {
-                    let sample_json: serde_json::Value = serde_json::from_str(&content)?;
-                    let output_json_path: &std::path::Path = self.standard_json_input_layout_path.as_ref();
-                    
-                    match fs::write(output_json_path, serde_json::to_string_pretty(&sample_json)?) {
-                        Ok(_) => {
-                            println!("Generated standard_json_input_layout.json");
-                        },
-                        Err(err) => {
-                            panic!("Error writing standard_json_input_layout_path: {}", err);
-                        }
+    fn ensure_owned(&self, action: &str) -> Result<(), ExtractorError> {
+        match self.source {
+            RepoSource::GitHub => Ok(()),
+            RepoSource::Local => Err(ExtractorError::Fetch(format!("Refusing to {} the local checkout {}.", action, self.identifier_path.display()))),
+        }
+    }
+
+    // solc standard JSON input for the layout: the ast of Schema.sol (and its imports), with the guest repo's remappings.
+    // Note: nothing is generated into the sources; ASTLayout lays the schema structs out from the ast.
+    pub fn gen_standard_json_input(&self) -> Result<(), ExtractorError> {
+        let schema_source = self.schema_source()?;
+        let mut sources = serde_json::Map::new();
+        sources.insert(schema_source.clone(), json!({ "urls": [schema_source] }));
+        let input = json!({
+            "language": "Solidity",
+            "settings": {
+                "outputSelection": {
+                    "*": {
+                        "": ["ast"]
                     }
                 },
-                Err(err) => {
-                    panic!("Error reading standard_json_input_layout_path: {}", err);
-                }
-            };
-        } else {
-            panic!("standard_json_input_layout_path({}) isn't exist.", self.standard_json_input_layout_path.to_str().unwrap());            
+                "remappings": self.remappings()?
+            },
+            "sources": sources
+        });
+
+        if let Some(dir) = self.standard_json_input_path.parent() {
+            fs::create_dir_all(dir).map_err(|e| ExtractorError::Compile(format!("Failed to create {}: {}", dir.display(), e)))?;
         }
+        fs::write(&self.standard_json_input_path, serde_json::to_string_pretty(&input).unwrap())
+            .map_err(|e| ExtractorError::Compile(format!("Error writing standard_json_input_path: {}", e)))?;
+        eprintln!("Generated {}", self.standard_json_input_path.display());
         Ok(())
     }
 
-    pub fn load_perf_config(&self) -> Result<HashMap<usize, HashMap<String, usize>>, Box<dyn Error>> {
+    // Source unit name of the bundle's Schema.sol: src/<bundle>/storages/Schema.sol, or the first one found under src/<bundle>.
+    pub fn schema_source(&self) -> Result<String, ExtractorError> {
+        let schema_path = if self.schema_path.exists() {
+            self.schema_path.clone()
+        } else {
+            let bundle_path = self.identifier_path.join("src").join(&self.bundle);
+            Self::find_file(&bundle_path, "Schema.sol")
+                .ok_or(ExtractorError::Compile(format!("No Schema.sol under {}", bundle_path.display())))?
+        };
+        let relative = schema_path.strip_prefix(&self.identifier_path)
+            .map_err(|_| ExtractorError::Compile(format!("{} is outside {}", schema_path.display(), self.identifier_path.display())))?;
+        Ok(relative.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect::<Vec<String>>().join("/"))
+    }
+
+    fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(dir).ok()?.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+        entries.sort();
+        if let Some(found) = entries.iter().find(|path| path.is_file() && path.file_name().map(|n| n == name).unwrap_or(false)) {
+            return Some(found.clone());
+        }
+        entries.iter().filter(|path| path.is_dir()).find_map(|path| Self::find_file(path, name))
+    }
+
+    // remappings.txt, else [profile.default] remappings of foundry.toml; bundle/=src/ is always there for Schema.sol's imports.
+    pub fn remappings(&self) -> Result<Vec<String>, ExtractorError> {
+        let remappings_txt = self.identifier_path.join("remappings.txt");
+        let foundry_toml = self.identifier_path.join("foundry.toml");
+        let mut remappings: Vec<String> = if remappings_txt.exists() {
+            let content = fs::read_to_string(&remappings_txt).map_err(|e| ExtractorError::Compile(format!("Failed to read {}: {}", remappings_txt.display(), e)))?;
+            content.lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.to_string())
+                .collect()
+        } else if foundry_toml.exists() {
+            let content = fs::read_to_string(&foundry_toml).map_err(|e| ExtractorError::Compile(format!("Failed to read {}: {}", foundry_toml.display(), e)))?;
+            let foundry: toml::Value = toml::from_str(&content).map_err(|e| ExtractorError::Compile(format!("Failed to parse {}: {}", foundry_toml.display(), e)))?;
+            foundry.get("profile").and_then(|profile| profile.get("default")).and_then(|default| default.get("remappings"))
+                .and_then(|remappings| remappings.as_array())
+                .map(|remappings| remappings.iter().filter_map(|remapping| remapping.as_str().map(|r| r.to_string())).collect())
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        if !remappings.iter().any(|remapping| remapping.starts_with("bundle/=")) {
+            remappings.insert(0, "bundle/=src/".to_string());
+        }
+        Ok(remappings)
+    }
+
+    pub fn load_perf_config(&self) -> Result<HashMap<usize, Constraint>, ExtractorError> {
         let mut _constraints: HashMap<usize, Constraint> = HashMap::new();
+        if self.docs.is_empty() {
+            return Err(ExtractorError::Config(format!("{} isn't loaded. Run fetch first.", self.perf_config_path.display())));
+        }
         if let Some(constraints) = self.docs[0]["constraints"].as_hash() {
             for (key, value) in constraints {
                 if let Yaml::String(key_str) = key {
@@ -160,28 +345,23 @@ impl MCRepoFetcher {
                     let constraint_cid = ConfigUtil::calc_id(constraint_class_paths);
 
                     if let Yaml::Hash(hash) = value {
-                        /*
-                            let conf = Config::new();
-                        */ 
-                        let _constraint = Constraint::new(constraint_cid.clone());
+                        let mut _constraint = Constraint::new(constraint_cid);
                         for (sub_key, sub_value) in hash {
                             if let (Yaml::String(sub_key_str), Yaml::String(sub_value_str)) = (sub_key, sub_value) {
                                 let expanded_target = self.resolve_user_defined_vars(sub_key_str.clone());
 
-                                // Note: [1] ParseTree must be returned and stored to registry.
-                                if (sub_value_str.clone() == "from") {
-                                    _constraint.from = Some(ConfigUtil::parse_config(expanded_target));
-                                } else if (sub_value_str.clone() == "to") {
-                                    _constraint.to = Some(ConfigUtil::parse_config(expanded_target));
+                                // Note: the parsed expression is stored to registry and evaluated once its fullnames are read.
+                                if sub_value_str == "from" {
+                                    _constraint.from = Some(ConfigUtil::parse_config(expanded_target)?);
+                                } else if sub_value_str == "to" {
+                                    _constraint.to = Some(ConfigUtil::parse_config(expanded_target)?);
                                 } else {
-                                    panic!("Unknown config field: {}", expanded_target.clone());
-                                }
-                                
-                                if !_constraints.contains_key(&constraint_cid) {
-                                    _constraints.insert(constraint_cid, _constraint.clone());
+                                    return Err(ExtractorError::Config(format!("Unknown config field: {}", expanded_target)));
                                 }
                             }
                         }
+                        // Note: insert once both from and to are set
+                        _constraints.insert(constraint_cid, _constraint);
                     }
                 }
             }
@@ -190,17 +370,16 @@ impl MCRepoFetcher {
     }
 
     // It apply var declaration up-side-down direction (latter defined var applied first)
-    fn resolve_user_defined_vars(&self, expr: String)->String{
+    pub fn resolve_user_defined_vars(&self, expr: String)->String{
 
-        if let Some(vars) = self.docs[0]["vars"].as_hash() {
+        if let Some(vars) = self.docs.first().and_then(|doc| doc["vars"].as_hash()) {
             for (key, value) in vars.into_iter().rev() {
                 if let (Yaml::String(key_str), Yaml::String(value_str)) = (key, value) {
                     let original_expr = expr.clone();
                     let replaced_expr = expr.replace(key_str, value_str);
                     if original_expr != replaced_expr {
                         return replaced_expr.clone();
-                    } else {
-                    }
+                    } 
                 }
             }
         }
@@ -211,7 +390,9 @@ impl MCRepoFetcher {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::{tempdir, TempDir};
+    use std::env;
+    use tempfile::tempdir;
+    use serde_json::Value;
 
     fn initialize() -> MCRepoFetcher {
         dotenv::dotenv().ok();
@@ -222,7 +403,7 @@ mod tests {
         let identifier = env::var("REPO_IDENTIFIER").unwrap();
         let bundle = env::var("BUNDLE_NAME").unwrap();
 
-        let fetcher = MCRepoFetcher::new(identifier.clone(), bundle.clone(), Some(pathbuf_temppath));
+        let fetcher = MCRepoFetcher::with_repo_path(identifier.clone(), bundle.clone(), pathbuf_temppath, ".repo").unwrap();
 
 
         if let Err(_err) = std::fs::remove_dir_all(&fetcher.local_repo_path) {
@@ -233,16 +414,18 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "needs REPO_IDENTIFIER/BUNDLE_NAME in .env and network access"]
     fn test_new() {
         let fetcher = initialize();
     
         assert_eq!(fetcher.url, format!("https://github.com/{}.git", fetcher.identifier));
-        assert_eq!(fetcher.local_repo_path, fetcher.base_path.join(format!(".repo")));
+        assert_eq!(fetcher.local_repo_path, fetcher.base_path.join(".repo"));
         assert_eq!(fetcher.schema_path, fetcher.base_path.join(format!(".repo/{}/src/{}/storages/Schema.sol", fetcher.identifier, fetcher.bundle)));
-        assert_eq!(fetcher.dummy_path, fetcher.base_path.join(format!(".repo/{}/src/{}/storages/Dummy.sol", fetcher.identifier, fetcher.bundle)));
+        assert_eq!(fetcher.standard_json_input_path, fetcher.standard_json_input_layout_path);
     }
 
     #[test]
+    #[ignore = "needs REPO_IDENTIFIER/BUNDLE_NAME in .env and network access"]
     fn test_clone_repo() {
         let fetcher = initialize();
     
@@ -254,28 +437,188 @@ mod tests {
     }
 
     #[test]
+    #[ignore = "needs REPO_IDENTIFIER/BUNDLE_NAME in .env and network access"]
     fn test_gen_standard_json_input() {
         let fetcher = initialize();
 
         if !fetcher.local_repo_path.exists() {
             std::fs::create_dir_all(fetcher.local_repo_path.clone()).unwrap();
         }
-        if !fetcher.standard_json_input_layout_path.exists() {
-            let copy_source = env::current_dir().unwrap().join(PathBuf::from(env::var("REPO_PATH").unwrap()).join(env::var("STANDARD_JSON_INPUT_LAYOUT_SAMPLE_NAME").unwrap()));
-            fs::copy(copy_source.clone(), &fetcher.standard_json_input_layout_path).unwrap();    
-        }
 
+        // Note: the input is generated from the checkout now, so it needs a Schema.sol to point at
+        fs::create_dir_all(fetcher.schema_path.parent().unwrap()).unwrap();
+        fs::write(&fetcher.schema_path, "// schema").unwrap();
         fetcher.gen_standard_json_input().unwrap();
 
-        let output_json_path = fetcher.local_repo_path.join(env::var("STANDARD_JSON_INPUT_LAYOUT_NAME").unwrap());
-        let output_json_content = fs::read_to_string(&output_json_path).unwrap();
+        let output_json_content = fs::read_to_string(&fetcher.standard_json_input_layout_path).unwrap();
         let output_json: serde_json::Value = serde_json::from_str(&output_json_content).unwrap();
 
-        assert_eq!(output_json["sources"].as_object().unwrap().len(), 2);
+        assert_eq!(output_json["sources"].as_object().unwrap().len(), 1);
         assert!(output_json["sources"].as_object().unwrap().contains_key(&format!("src/{}/storages/Schema.sol", fetcher.bundle)));
-        assert!(output_json["sources"].as_object().unwrap().contains_key(&format!("src/{}/storages/Dummy.sol", fetcher.bundle)));
+        assert!(!fetcher.schema_path.with_file_name("Dummy.sol").exists());
+
+    }
+
+    #[test]
+    fn test_local_checkout_is_left_untouched() {
+        let base_path = tempdir().unwrap().into_path();
+        let checkout = tempdir().unwrap().into_path();
+        let storages = checkout.join("src/textDAO/storages");
+        fs::create_dir_all(&storages).unwrap();
+        fs::write(storages.join("Schema.sol"), "// schema").unwrap();
+        fs::write(storages.join("Indexer.yaml"), "constraints: {}").unwrap();
+
+        let mut fetcher = MCRepoFetcher::local(checkout.clone(), "textDAO".to_string(), base_path, ".repo").unwrap();
+        assert!(fetcher.is_fetched());
+        fetcher.fetch().unwrap();
+        assert_eq!(fetcher.docs.len(), 1);
+        assert!(fetcher.clone_repo().is_err());
+        assert!(fetcher.update_repo().is_err());
+
+        fetcher.gen_standard_json_input().unwrap();
+
+        assert_eq!(fs::read_dir(&storages).unwrap().count(), 2);
+        assert!(!fetcher.standard_json_input_path.starts_with(&checkout));
+        let input: serde_json::Value = serde_json::from_str(&fs::read_to_string(&fetcher.standard_json_input_path).unwrap()).unwrap();
+        assert_eq!(input["sources"]["src/textDAO/storages/Schema.sol"]["urls"], json!(["src/textDAO/storages/Schema.sol"]));
+    }
 
+    #[test]
+    fn test_checkout_pins_ref() {
+        let origin_path = tempdir().unwrap().into_path();
+        let origin = Repository::init(&origin_path).unwrap();
+        let signature = git2::Signature::now("indexer", "indexer@example.com").unwrap();
+        let commit_schema = |content: &str, message: &str| {
+            fs::create_dir_all(origin_path.join("src/textDAO/storages")).unwrap();
+            fs::write(origin_path.join("src/textDAO/storages/Schema.sol"), content).unwrap();
+            let mut index = origin.index().unwrap();
+            index.add_path(Path::new("src/textDAO/storages/Schema.sol")).unwrap();
+            index.write().unwrap();
+            let tree = origin.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<git2::Commit> = origin.head().ok().map(|head| head.peel_to_commit().unwrap()).into_iter().collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            origin.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap()
+        };
+        let v1 = commit_schema("// v1", "v1");
+        origin.tag_lightweight("v1", &origin.find_object(v1, None).unwrap(), false).unwrap();
+        let v2 = commit_schema("// v2", "v2");
+
+        let mut fetcher = MCRepoFetcher::with_repo_path("org/repo".to_string(), "textDAO".to_string(), tempdir().unwrap().into_path(), ".repo").unwrap()
+            .with_ref(Some("v1".to_string()));
+        fetcher.url = origin_path.display().to_string();
+        fetcher.clone_repo().unwrap();
+        assert_eq!(fetcher.checkout().unwrap(), v1.to_string());
+        assert_eq!(fs::read_to_string(&fetcher.schema_path).unwrap(), "// v1");
+
+        // A new upstream commit doesn't move a pinned tag, and the default branch follows it after update_repo
+        let v3 = commit_schema("// v3", "v3");
+        fetcher.update_repo().unwrap();
+        assert_eq!(fetcher.checkout().unwrap(), v1.to_string());
+        fetcher.git_ref = Some(v2.to_string());
+        assert_eq!(fetcher.checkout().unwrap(), v2.to_string());
+        fetcher.git_ref = None;
+        fetcher.checkout().unwrap();
+        assert_eq!(fetcher.head_commit(), Some(v3.to_string()));
     }
 
+    #[test]
+    fn test_clone_repo_never_deletes() {
+        let origin_path = tempdir().unwrap().into_path();
+        let origin = Repository::init(&origin_path).unwrap();
+        let signature = git2::Signature::now("indexer", "indexer@example.com").unwrap();
+        let commit_readme = |content: &str| {
+            fs::write(origin_path.join("README.md"), content).unwrap();
+            let mut index = origin.index().unwrap();
+            index.add_path(Path::new("README.md")).unwrap();
+            index.write().unwrap();
+            let tree = origin.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<git2::Commit> = origin.head().ok().map(|head| head.peel_to_commit().unwrap()).into_iter().collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            origin.commit(Some("HEAD"), &signature, &signature, content, &tree, &parents).unwrap()
+        };
+        commit_readme("v1");
+
+        let mut fetcher = MCRepoFetcher::with_repo_path("org/repo".to_string(), "textDAO".to_string(), tempdir().unwrap().into_path(), ".repo").unwrap();
+        fetcher.url = origin_path.display().to_string();
+
+        // A directory that isn't a clone is refused, not replaced
+        fs::create_dir_all(&fetcher.identifier_path).unwrap();
+        fs::write(fetcher.identifier_path.join("notes.txt"), "mine").unwrap();
+        assert!(fetcher.clone_repo().is_err());
+        assert_eq!(fs::read_to_string(fetcher.identifier_path.join("notes.txt")).unwrap(), "mine");
 
-}
\ No newline at end of file
+        // An existing clone is fetched into, keeping what's in its worktree
+        fs::remove_file(fetcher.identifier_path.join("notes.txt")).unwrap();
+        fetcher.clone_repo().unwrap();
+        fs::write(fetcher.identifier_path.join("notes.txt"), "mine").unwrap();
+        let v2 = commit_readme("v2");
+        fetcher.clone_repo().unwrap();
+        assert_eq!(fs::read_to_string(fetcher.identifier_path.join("notes.txt")).unwrap(), "mine");
+        assert_eq!(fetcher.checkout().unwrap(), v2.to_string());
+    }
+
+    #[test]
+    fn test_checkout_updates_pinned_submodules() {
+        let signature = git2::Signature::now("indexer", "indexer@example.com").unwrap();
+        let commit_all = |repo: &Repository, message: &str| {
+            let mut index = repo.index().unwrap();
+            index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None).unwrap();
+            index.write().unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<git2::Commit> = repo.head().ok().map(|head| head.peel_to_commit().unwrap()).into_iter().collect();
+            let parents: Vec<&git2::Commit> = parents.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap()
+        };
+
+        // lib/mc at v1 while its upstream already moved on to v2
+        let dep_path = tempdir().unwrap().into_path();
+        let dep = Repository::init(&dep_path).unwrap();
+        fs::write(dep_path.join("Lib.sol"), "// v1").unwrap();
+        let v1 = commit_all(&dep, "v1");
+        fs::write(dep_path.join("Lib.sol"), "// v2").unwrap();
+        commit_all(&dep, "v2");
+
+        let origin_path = tempdir().unwrap().into_path();
+        let origin = Repository::init(&origin_path).unwrap();
+        fs::create_dir_all(origin_path.join("src/textDAO/storages")).unwrap();
+        fs::write(origin_path.join("src/textDAO/storages/Schema.sol"), "// schema").unwrap();
+        fs::write(origin_path.join("src/textDAO/storages/Indexer.yaml"), "constraints: {}").unwrap();
+        let mut submodule = origin.submodule(&dep_path.display().to_string(), Path::new("lib/mc"), true).unwrap();
+        let sub_repo = submodule.clone(None).unwrap();
+        sub_repo.set_head_detached(v1).unwrap();
+        sub_repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        submodule.add_to_index(true).unwrap();
+        submodule.add_finalize().unwrap();
+        commit_all(&origin, "schema");
+
+        let cache = tempdir().unwrap().into_path();
+        let mut fetcher = MCRepoFetcher::with_repo_path("org/repo".to_string(), "textDAO".to_string(), tempdir().unwrap().into_path(), ".repo").unwrap()
+            .with_submodule_cache(Some(cache.clone()));
+        fetcher.url = origin_path.display().to_string();
+        fetcher.fetch().unwrap();
+
+        assert_eq!(fs::read_to_string(fetcher.identifier_path.join("lib/mc/Lib.sol")).unwrap(), "// v1");
+        let mirror = cache.join(format!("{}.git", dep_path.display().to_string().trim_start_matches('/')));
+        assert!(Repository::open_bare(&mirror).unwrap().find_commit(v1).is_ok());
+    }
+
+    #[test]
+    fn test_gen_standard_json_input_from_repo() {
+        let checkout = tempdir().unwrap().into_path();
+        fs::create_dir_all(checkout.join("src/myDAO/schema")).unwrap();
+        fs::write(checkout.join("src/myDAO/schema/Schema.sol"), "// schema").unwrap();
+        fs::write(checkout.join("foundry.toml"), "[profile.default]\nsrc = \"src\"\nremappings = [\"@mc/=lib/mc/src/\", \"forge-std/=lib/forge-std/src/\"]\n").unwrap();
+
+        let fetcher = MCRepoFetcher::local(checkout.clone(), "myDAO".to_string(), tempdir().unwrap().into_path(), ".repo").unwrap();
+        assert_eq!(fetcher.remappings().unwrap(), vec!["bundle/=src/", "@mc/=lib/mc/src/", "forge-std/=lib/forge-std/src/"]);
+
+        fetcher.gen_standard_json_input().unwrap();
+        let input: Value = serde_json::from_str(&fs::read_to_string(&fetcher.standard_json_input_path).unwrap()).unwrap();
+        assert_eq!(input["sources"]["src/myDAO/schema/Schema.sol"]["urls"], json!(["src/myDAO/schema/Schema.sol"]));
+        assert_eq!(input["settings"]["remappings"], json!(["bundle/=src/", "@mc/=lib/mc/src/", "forge-std/=lib/forge-std/src/"]));
+
+        // remappings.txt wins over foundry.toml
+        fs::write(checkout.join("remappings.txt"), "# deps\nbundle/=src/\n@oz/=lib/openzeppelin-contracts/\n\n").unwrap();
+        assert_eq!(fetcher.remappings().unwrap(), vec!["bundle/=src/", "@oz/=lib/openzeppelin-contracts/"]);
+    }
+}
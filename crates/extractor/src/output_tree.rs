@@ -0,0 +1,53 @@
+use super::registry::Registry;
+
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value;
+
+pub struct OutputTree;
+
+impl OutputTree {
+    // Scanned primitives nested by their instance path, e.g. {"Foo": {"pairs": {"0": {"x": "160"}}}}.
+    // Leaves are decoded values, or the raw hex when a value couldn't be decoded.
+    pub fn build(registry: &Registry) -> Value {
+        let mut root = Map::new();
+        for (id, e) in registry.output_flatten.iter() {
+            let leaf = match (registry.decoded.get(id), registry.values.get(id)) {
+                (Some(decoded), _) => json!(decoded.to_string()),
+                (None, Some(value)) => json!(format!("0x{}", value)),
+                (None, None) => continue,
+            };
+
+            let paths = e.instance_paths();
+            let (last, parents) = match paths.split_last() {
+                Some(split) => split,
+                None => continue,
+            };
+            let mut node = &mut root;
+            for path in parents {
+                let child = node.entry(path.clone()).or_insert_with(|| Value::Object(Map::new()));
+                if !child.is_object() {
+                    *child = Value::Object(Map::new());
+                }
+                node = child.as_object_mut().unwrap();
+            }
+            node.insert(last.clone(), leaf);
+        }
+        Value::Object(root)
+    }
+
+    // The tree plus what it was read from (block, guest repo commit) and what got left out.
+    pub fn document(registry: &Registry) -> Value {
+        let mut skipped: Vec<Value> = registry.skipped.values()
+            .map(|(fullname, err)| json!({ "path": fullname, "error": err.to_string() }))
+            .collect();
+        skipped.sort_by_key(|entry| entry["path"].as_str().unwrap_or_default().to_string());
+
+        json!({
+            "block": registry.block.as_ref().map(|block| block.to_string()),
+            "commit": registry.commit,
+            "values": Self::build(registry),
+            "skipped": skipped,
+        })
+    }
+}
@@ -0,0 +1,171 @@
+use super::storage_layout::StorageLayout;
+use super::slot_util::SlotUtil;
+use super::type_id::TypeId;
+use super::error::ExtractorError;
+
+use serde_json::Value;
+
+// Where a node lives. Slots below a mapping or a dynamic array depend on keys/lengths only a scan knows.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlannedSlot {
+    Absolute(String),
+    Symbolic(String),
+}
+
+impl PlannedSlot {
+    fn add(&self, number: usize) -> Result<PlannedSlot, ExtractorError> {
+        match self {
+            PlannedSlot::Absolute(slot) => SlotUtil::add_to_slot(slot, number).map(PlannedSlot::Absolute).map_err(ExtractorError::Slot),
+            PlannedSlot::Symbolic(slot) if number == 0 => Ok(PlannedSlot::Symbolic(slot.clone())),
+            PlannedSlot::Symbolic(slot) => Ok(PlannedSlot::Symbolic(format!("{} + {}", slot, number))),
+        }
+    }
+    fn describe(&self) -> String {
+        match self {
+            PlannedSlot::Absolute(slot) => format!("0x{}", SlotUtil::normalize_slot(slot)),
+            PlannedSlot::Symbolic(slot) => slot.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for PlannedSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.describe())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanEntry {
+    pub depth: usize,
+    pub path: String, // instance path with [key]/[i] placeholders, e.g. Foo.proposals[i].title
+    pub label: String,
+    pub type_id: String,
+    pub slot: PlannedSlot,
+    pub offset: String, // bytes from the lower-order end; symbolic for packed array elements
+}
+
+// Traversal tree of a layout, computed from the types table alone (no RPC).
+pub struct Planner;
+
+impl Planner {
+    // Note: a scan executes depth `step + 1` at each step, so nothing deeper than `max_steps` (limits.max_steps) is visited.
+    pub fn plan(layout: &StorageLayout, max_steps: usize) -> Result<Vec<PlanEntry>, ExtractorError> {
+        let types = layout.types();
+        let mut roots: Vec<(&String, &String)> = layout.base_slots.iter().collect();
+        roots.sort();
+
+        let mut entries = Vec::new();
+        for (name, base_slot) in roots {
+            let type_id = Self::root_type_id(types, name)?;
+            Self::walk(types, &type_id, name.clone(), PlannedSlot::Absolute(base_slot.clone()), "0".to_string(), 0, max_steps, &mut entries)?;
+        }
+        Ok(entries)
+    }
+
+    fn root_type_id(types: &Value, name: &str) -> Result<String, ExtractorError> {
+        let types = types.as_object().ok_or(ExtractorError::Layout("storageLayout has no types".to_string()))?;
+        for type_id in types.keys() {
+            if let Ok(TypeId::Struct { name: struct_name, .. }) = TypeId::parse(type_id) {
+                if struct_name == name {
+                    return Ok(type_id.clone());
+                }
+            }
+        }
+        Err(ExtractorError::Layout(format!("No struct {} in the storageLayout types", name)))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn walk(types: &Value, type_id: &str, path: String, slot: PlannedSlot, offset: String, depth: usize, max_steps: usize, entries: &mut Vec<PlanEntry>) -> Result<(), ExtractorError> {
+        if depth > max_steps {
+            return Ok(());
+        }
+        let type_info = types.get(type_id).ok_or(ExtractorError::Layout(format!("{} is not in the storageLayout types", type_id)))?;
+        entries.push(PlanEntry {
+            depth,
+            path: path.clone(),
+            label: type_info["label"].as_str().unwrap_or_default().to_string(),
+            type_id: type_id.to_string(),
+            slot: slot.clone(),
+            offset,
+        });
+
+        match TypeId::parse(type_id)? {
+            TypeId::Struct { .. } => {
+                for member in type_info["members"].as_array().cloned().unwrap_or_default() {
+                    let (member_type, label, member_offset, member_slot) = match (member["type"].as_str(), member["label"].as_str(), member["offset"].as_u64(), member["slot"].as_str().and_then(|s| s.parse::<usize>().ok())) {
+                        (Some(member_type), Some(label), Some(member_offset), Some(member_slot)) => (member_type, label, member_offset, member_slot),
+                        _ => return Err(ExtractorError::Layout(format!("{} has an invalid member: {}", type_id, member))),
+                    };
+                    Self::walk(types, member_type, format!("{}.{}", path, label), slot.add(member_slot)?, member_offset.to_string(), depth + 1, max_steps, entries)?;
+                }
+            },
+            TypeId::Mapping { value, .. } => {
+                let value_slot = PlannedSlot::Symbolic(format!("keccak256(key . {})", slot));
+                Self::walk(types, &value.to_string(), format!("{}[key]", path), value_slot, "0".to_string(), depth + 1, max_steps, entries)?;
+            },
+            TypeId::Array { base, length, .. } => {
+                let base_type = base.to_string();
+                let number_of_bytes = types[&base_type]["numberOfBytes"].as_str().and_then(|n| n.parse::<usize>().ok())
+                    .ok_or(ExtractorError::Layout(format!("{} has no numberOfBytes", base_type)))?;
+                let first_element = match length {
+                    None => format!("keccak256({})", slot),
+                    Some(_) => slot.to_string(),
+                };
+                // See SlotUtil::element_position
+                let (element_slot, element_offset) = if number_of_bytes > 0 && number_of_bytes <= 16 {
+                    let per_slot = 32 / number_of_bytes;
+                    (format!("{} + i / {}", first_element, per_slot), format!("(i % {}) * {}", per_slot, number_of_bytes))
+                } else {
+                    (format!("{} + i * {}", first_element, SlotUtil::slots_of(number_of_bytes)), "0".to_string())
+                };
+                Self::walk(types, &base_type, format!("{}[i]", path), PlannedSlot::Symbolic(element_slot), element_offset, depth + 1, max_steps, entries)?;
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use serde_json::json;
+
+    #[test]
+    fn test_plan_without_rpc() {
+        let types = test_support::types(json!({
+            "t_struct(Foo)1_storage": {
+                "encoding": "inplace",
+                "label": "struct Schema.Foo",
+                "numberOfBytes": "64",
+                "members": [
+                    { "astId": 2, "label": "owner", "offset": 0, "slot": "0", "type": "t_address" },
+                    { "astId": 3, "label": "balances", "offset": 0, "slot": "1", "type": "t_mapping(t_address,t_array(t_uint128)dyn_storage)" }
+                ]
+            },
+            "t_mapping(t_address,t_array(t_uint128)dyn_storage)": {
+                "encoding": "mapping", "label": "mapping(address => uint128[])", "numberOfBytes": "32",
+                "key": "t_address", "value": "t_array(t_uint128)dyn_storage"
+            },
+            "t_array(t_uint128)dyn_storage": { "encoding": "dynamic_array", "label": "uint128[]", "numberOfBytes": "32", "base": "t_uint128" }
+        }));
+        let layout = test_support::layout(types, &[("Foo", "0x100")]);
+
+        let entries = Planner::plan(&layout, 16).unwrap();
+        let lines: Vec<(String, String, String)> = entries.iter().map(|e| (e.path.clone(), e.slot.to_string(), e.offset.clone())).collect();
+        let slot = |n: &str| format!("0x{:0>64}", n);
+        assert_eq!(lines, vec![
+            ("Foo".to_string(), slot("100"), "0".to_string()),
+            ("Foo.owner".to_string(), slot("100"), "0".to_string()),
+            ("Foo.balances".to_string(), slot("101"), "0".to_string()),
+            ("Foo.balances[key]".to_string(), format!("keccak256(key . {})", slot("101")), "0".to_string()),
+            ("Foo.balances[key][i]".to_string(), format!("keccak256(keccak256(key . {})) + i / 2", slot("101")), "(i % 2) * 16".to_string()),
+        ]);
+        assert_eq!(entries[4].depth, 3);
+
+        // Elements of Foo.balances[key] take a third step
+        let paths: Vec<String> = Planner::plan(&layout, 2).unwrap().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["Foo", "Foo.owner", "Foo.balances", "Foo.balances[key]"]);
+    }
+}
@@ -0,0 +1,91 @@
+use super::eth_call::EthCall;
+use super::block_id::BlockId;
+use super::error::ExtractorError;
+
+pub const DEFAULT_RPC_URL: &str = "http://127.0.0.1:8545";
+pub const DEFAULT_CHAIN_ID: i64 = 31337; // anvil and hardhat, which DEFAULT_RPC_URL points at
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReadStrategy {
+    CodeOverride, // eth_call against CONTRACT_CODE injected via state override
+    StorageAt,    // batched eth_getStorageAt, for nodes rejecting state overrides
+}
+
+impl ReadStrategy {
+    pub fn parse(strategy: &str) -> Result<Self, ExtractorError> {
+        match strategy {
+            "override" => Ok(ReadStrategy::CodeOverride),
+            "storage_at" => Ok(ReadStrategy::StorageAt),
+            _ => Err(ExtractorError::Config(format!("Unknown READ_STRATEGY: {} (expected override or storage_at)", strategy))),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Provider {
+    pub rpc_url: String,
+    pub chain_id: i64,
+    pub network: Option<String>, // resolved from chainIds.json; None for dev and unlisted chains
+    pub contract_addr: String,
+    pub contract_code: Option<String>, // only needed by ReadStrategy::CodeOverride
+    pub read_strategy: ReadStrategy,
+    pub block: Option<BlockId>, // None means "resolve latest when the scan starts"
+}
+
+impl Provider {
+    pub fn new(rpc_url: String, chain_id: i64, contract_addr: String, contract_code: Option<String>, read_strategy: ReadStrategy, block: Option<BlockId>) -> Result<Self, ExtractorError> {
+        if read_strategy == ReadStrategy::CodeOverride && contract_code.is_none() {
+            return Err(ExtractorError::Config("CONTRACT_CODE was not provided. It is required by the override reader; set READ_STRATEGY=storage_at otherwise.".to_string()));
+        }
+        let network = EthCall::get_network_name(chain_id)?;
+
+        Ok(Self {
+            rpc_url,
+            chain_id,
+            network,
+            contract_addr,
+            contract_code,
+            read_strategy,
+            block,
+        })
+    }
+
+    pub fn network_name(&self) -> String {
+        self.network.clone().unwrap_or_else(|| format!("chain-{}", self.chain_id))
+    }
+
+    pub async fn verify_chain_id(&self) -> Result<(), ExtractorError> {
+        let remote_chain_id = EthCall::get_chain_id(&self.rpc_url).await?;
+        if remote_chain_id != self.chain_id {
+            return Err(ExtractorError::Rpc(format!(
+                "{} answered eth_chainId={} but the provider is configured for {}({}).",
+                self.rpc_url, remote_chain_id, self.network_name(), self.chain_id
+            )));
+        }
+        Ok(())
+    }
+
+    // Resolve the single block every extraction step reads from.
+    pub async fn resolve_block(&self) -> Result<BlockId, ExtractorError> {
+        match &self.block {
+            Some(block) => Ok(block.clone()),
+            None => Ok(BlockId::Number(EthCall::get_block_number(&self.rpc_url).await?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_name() {
+        let provider = |chain_id| Provider::new(DEFAULT_RPC_URL.to_string(), chain_id, "0x00".to_string(), None, ReadStrategy::StorageAt, None).unwrap();
+
+        assert_eq!(provider(1).network_name(), "ethereum");
+        // Dev chains aren't in chainIds.json; they still get a provider
+        assert_eq!(provider(31337).network, None);
+        assert_eq!(provider(31337).network_name(), "chain-31337");
+        assert_eq!(provider(11155111).network_name(), "chain-11155111");
+    }
+}
@@ -1,30 +1,27 @@
-use super::compiler::Compiler;
-use super::extractor::Extractor;
-use super::executor::Executor;
 // use super::registry::Registry;
 use super::executable::Executable;
-use super::config_util::ConfigUtil;
+use super::config_util::{ConfigExpr, ConfigUtil};
 use super::type_kind::TypeKind;
-use super::eth_call::EthCall;
-use super::perf_expression_evaluator::PerfExpressionEvaluator;
 use super::ast_node::ASTNode;
-use super::mc_repo_fetcher::MCRepoFetcher;
+use super::block_id::BlockId;
+use super::slot_util::SlotUtil;
+use super::value_decoder::{TypedValue, ValueDecoder};
+use super::error::ExtractorError;
 
 use std::collections::HashMap;
-use std::process::Command;
 use serde_json::Value;
-use std::error::Error;
 use std::result::Result;
 use std::result::Result::{Ok, Err};
 
-use bnf::ParseTree;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
 
 
 #[derive(Clone)]
 pub struct Constraint {
     pub cid: usize,
-    pub from: Option<Box<Vec<ParseTree>>>,
-    pub to: Option<Box<Vec<ParseTree>>>,
+    pub from: Option<ConfigExpr>,
+    pub to: Option<ConfigExpr>,
 }
 impl Constraint {
     pub fn new(cid: usize) -> Self {
@@ -44,12 +41,19 @@ pub struct Registry {
     pub iterish_from_to: HashMap<usize, (usize, usize)>, // key=ast_id
     pub output_flatten: HashMap<usize, Executable>, // key=ast_id
     pub types: Value, // ast info
+    pub storage: Value, // the schema structs at their base slots, [{label, slot, type}] as in storageLayout
     pub absolute_slots: HashMap<usize, String>, // key=step, ast_id
-    pub values: HashMap<usize, String>, // key=ast_id
+    pub values: HashMap<usize, String>, // key=ast_id, primitives are cut out of their word by offset and numberOfBytes
+    pub words: HashMap<String, String>, // key=normalized slot, raw 32-byte word
+    pub decoded: HashMap<usize, TypedValue>, // key=ast_id, primitives only
+    pub enum_variants: HashMap<String, Vec<String>>, // key=enum type id
+    pub block: Option<BlockId>, // every entry of values was read at this block
+    pub commit: Option<String>, // guest repo commit the layout was compiled from
+    pub skipped: HashMap<usize, (String, ExtractorError)>, // key=ast_id, value=(fullname, why its subtree was dropped)
 }
 
 impl Registry {
-    pub fn new(blob:Value, constraints: HashMap<usize, Constraint>, bundle: String) -> Self {
+    pub fn new(blob:Value, constraints: HashMap<usize, Constraint>) -> Self {
 
         Self {
             queue_per_step: Vec::new(),
@@ -57,9 +61,16 @@ impl Registry {
             constraints,
             iterish_from_to: HashMap::new(),
             output_flatten: HashMap::new(),
-            types: blob["contracts"][format!("src/{}/storages/Dummy.sol", bundle.clone())]["Dummy"]["storageLayout"]["types"].clone(),
+            enum_variants: ASTNode::enum_variants(&blob),
+            types: blob["storageLayout"]["types"].clone(),
+            storage: blob["storageLayout"]["storage"].clone(),
             absolute_slots: HashMap::new(),
             values: HashMap::new(),
+            words: HashMap::new(),
+            decoded: HashMap::new(),
+            block: None,
+            commit: None,
+            skipped: HashMap::new(),
         }
     }
 
@@ -71,43 +82,97 @@ impl Registry {
         };
         self
     }
+    // Drop `e` and everything below it, keeping the reason for the report.
+    pub fn skip(&mut self, e: &Executable, err: ExtractorError) -> &mut Self {
+        eprintln!("Skipping {}: {}", e.fullname(), err);
+        self.skipped.insert(e.id, (e.fullname(), err));
+        self
+    }
+    pub fn is_skipped(&self, id: usize) -> bool {
+        self.skipped.contains_key(&id)
+    }
     #[allow(unused_mut)]
     pub fn bulk_fill_from_to(&mut self, pending_fillable_iterish: &HashMap<usize, Executable>) -> &mut Self {
         for (id, e) in pending_fillable_iterish {
             match self.eval_config(e.clone()) {
-                Ok((op_from_str, op_to_str)) => {
-                    self.iterish_from_to.insert(*id, (op_from_str.unwrap().parse::<usize>().unwrap(), op_to_str.unwrap().parse::<usize>().unwrap()));
+                Ok(from_to) => {
+                    self.iterish_from_to.insert(*id, from_to);
+                },
+                Err(err) => {
+                    self.skip(e, err);
+                },
+            };
+        };
+        self
+    }
+    // Note: a dynamic array's length is an on-chain word; above `max_array_length` (limits.max_array_length) it's skipped, not enumerated
+    pub fn bulk_fill_array_lengths(&mut self, arrays: &HashMap<usize, Executable>, max_array_length: usize) -> &mut Self {
+        for (id, e) in arrays {
+            let length = match e.type_kind {
+                TypeKind::FixedArray(length) => length,
+                _ => match self.values.get(id).and_then(|value| BigUint::parse_bytes(value.trim_start_matches("0x").as_bytes(), 16)).and_then(|len| len.to_usize()) {
+                    Some(length) if length > max_array_length => {
+                        let err = ExtractorError::Slot(format!("Array length {} exceeds limits.max_array_length ({})", length, max_array_length));
+                        self.skip(e, err);
+                        continue;
+                    },
+                    Some(length) => length,
+                    None => {
+                        let err = ExtractorError::Slot(format!("Invalid array length: {:?}", self.values.get(id)));
+                        self.skip(e, err);
+                        continue;
+                    },
                 },
-                Err(err) => panic!("{}", err),
             };
+            self.iterish_from_to.insert(*id, (0, length));
         };
         self
     }
-    fn get_iid(&self, from_length_target_cid: usize) -> Result<usize, Box<dyn Error>> {
+    fn get_iid(&self, from_length_target_cid: usize) -> Result<usize, ExtractorError> {
         for (iid, e) in self.visited.clone() { // ast_instance_id
             if e.cid() == from_length_target_cid {
                 return Ok(iid);
             }
         }
-        panic!("target_cid:{} hasn't visited yet.", from_length_target_cid);
+        Err(ExtractorError::Config(format!("target_cid:{} hasn't visited yet.", from_length_target_cid)))
     }
-    pub fn eval_config(&self, e: Executable)-> Result<(Option<String>, Option<String>), Box<dyn Error>> {
+    pub fn eval_config(&self, e: Executable) -> Result<(usize, usize), ExtractorError> {
         // Ref: mc_repo_fetcher:L137
         let constraint_cid = e.cid();
-        if e.is_iterish() && !self.constraints.contains_key(&constraint_cid) {
-            panic!("{} is iterish node in the guest protocol schema and was not in constraints definition in Indexer.yaml of the guest protocol repo. Please consider adding {} to Indexer.yaml", e.fullname(), e.fullname_in_conf());        
-        } else if !self.constraints.contains_key(&constraint_cid) {
-            panic!("{} was not in constraints definition in Indexer.yaml of the guest protocol repo.", e.fullname());
-        }
+        let constraint = match self.constraints.get(&constraint_cid) {
+            Some(constraint) => constraint,
+            None if e.is_iterish() => return Err(ExtractorError::Config(format!("{} is iterish node in the guest protocol schema and was not in constraints definition in Indexer.yaml of the guest protocol repo. Please consider adding {} to Indexer.yaml", e.fullname(), e.fullname_in_conf()))),
+            None => return Err(ExtractorError::Config(format!("{} was not in constraints definition in Indexer.yaml of the guest protocol repo.", e.fullname()))),
+        };
+        let (Some(from), Some(to)) = (&constraint.from, &constraint.to) else {
+            return Err(ExtractorError::Config(format!("{} needs both from and to in Indexer.yaml", e.fullname_in_conf())));
+        };
 
-        let from_stack = ConfigUtil::eval_parse_tree(self.constraints[&constraint_cid].from, None);
-        let to_stack = ConfigUtil::eval_parse_tree(self.constraints[&constraint_cid].to, None);
-        Ok((from_stack.get(0).cloned(), to_stack.get(0).cloned()))
+        // A fullname in from/to refers to a primitive that an earlier step has already read.
+        let resolve = |fullname: &str| -> Result<usize, ExtractorError> {
+            let iid = self.get_iid(ConfigUtil::calc_id(ConfigUtil::to_class_paths(fullname.to_string())))?;
+            self.values.get(&iid)
+                .and_then(|value| BigUint::parse_bytes(value.trim_start_matches("0x").as_bytes(), 16))
+                .and_then(|value| value.to_usize())
+                .ok_or(ExtractorError::Config(format!("{} hasn't got a usable value: {:?}", fullname, self.values.get(&iid))))
+        };
+        Ok((ConfigUtil::eval_config(from, &resolve)?, ConfigUtil::eval_config(to, &resolve)?))
     }
 
+    // Append to the queue of `step`, growing queue_per_step as needed.
+    fn enqueue(&mut self, step:usize, executables: Vec<Executable>) -> &mut Self {
+        while self.queue_per_step.len() <= step {
+            self.queue_per_step.push(Vec::new());
+        }
+        self.queue_per_step[step].extend(executables);
+        self
+    }
     pub fn bulk_enqueue_execution(&mut self, step:usize, executables: HashMap<usize, Executable>) -> &mut Self {
-        for (_, e) in executables.iter() {
-            self.queue_per_step.insert(step, vec![e.clone()]);
+        for (id, e) in executables.iter() {
+            if self.is_skipped(*id) {
+                continue;
+            }
+            self.enqueue(step, vec![e.clone()]);
         };
         self
     }
@@ -115,14 +180,17 @@ impl Registry {
     {
         let mut _self = self;
         {
-            let children = executable.children(&_self.clone(), None).unwrap();
-
-            _self.queue_per_step.insert(step, children);
-            _self
+            match executable.children(&_self.clone(), None) {
+                Ok(children) => _self.enqueue(step, children),
+                Err(err) => _self.skip(executable, err),
+            }
         }
     }
     pub fn bulk_enqueue_children_execution(&mut self, step:usize, filled_queueable_iterish: &HashMap<usize, Executable>) -> &mut Self {
-        for (_, e) in filled_queueable_iterish.iter() {
+        for (id, e) in filled_queueable_iterish.iter() {
+            if self.is_skipped(*id) {
+                continue;
+            }
             self.enqueue_children_execution(step, e);
         };
         self
@@ -133,9 +201,119 @@ impl Registry {
         };
         self
     }
-    pub fn bulk_save_values(&mut self, values:HashMap<usize, String>) -> &mut Self {
-        for (id, value) in values.iter() {
-            self.values.insert(*id, value.clone());
+    // One entry per slot that hasn't been read yet. All reads share one block, so a word never goes stale.
+    pub fn unread_slots(&self, absolute_slots: &HashMap<usize, String>) -> HashMap<usize, String> {
+        let mut unread: HashMap<String, usize> = HashMap::new();
+        for (id, slot) in absolute_slots.iter() {
+            let slot = SlotUtil::normalize_slot(slot);
+            if !self.words.contains_key(&slot) {
+                unread.entry(slot).or_insert(*id);
+            }
+        };
+        unread.into_iter().map(|(slot, id)| (id, slot)).collect()
+    }
+    pub fn bulk_save_words(&mut self, slots: &HashMap<usize, String>, words: HashMap<usize, String>) -> &mut Self {
+        for (id, word) in words.iter() {
+            if let Some(slot) = slots.get(id) {
+                self.words.insert(SlotUtil::normalize_slot(slot), word.clone());
+            }
+        };
+        self
+    }
+    // Slots holding the data of long string/bytes values. Keys only need to be unique for SlotReader.
+    pub fn payload_slots(&mut self, executed: &[Executable]) -> HashMap<usize, String> {
+        let mut payload_slots: HashMap<usize, String> = HashMap::new();
+        let bytes_encoded: Vec<&Executable> = executed.iter().filter(|e| self.is_bytes_encoded(e)).collect();
+        for e in bytes_encoded {
+            match self.long_payload_slots(e) {
+                Ok(slots) => {
+                    for slot in slots {
+                        payload_slots.insert(payload_slots.len(), slot);
+                    }
+                },
+                Err(err) => {
+                    self.skip(e, err);
+                },
+            }
+        };
+        payload_slots
+    }
+    fn long_payload_slots(&self, e: &Executable) -> Result<Vec<String>, ExtractorError> {
+        let (slot, word) = self.slot_word(e)?;
+        match ValueDecoder::bytes_length(word) {
+            Ok((length, true)) => {
+                let data_slot = SlotUtil::dynamic_array_data_slot(slot).map_err(ExtractorError::Slot)?;
+                (0..length.div_ceil(32)).map(|i| SlotUtil::add_to_slot(&data_slot, i).map_err(ExtractorError::Slot)).collect()
+            },
+            // Note: short values live in the word itself; an invalid length is reported by cut_value
+            _ => Ok(Vec::new()),
+        }
+    }
+    // The absolute slot of `e` and the word read from it.
+    fn slot_word(&self, e: &Executable) -> Result<(&String, &String), ExtractorError> {
+        let slot = self.absolute_slots.get(&e.id).ok_or(ExtractorError::Slot(format!("{} has no absolute slot", e.fullname())))?;
+        let word = self.words.get(&SlotUtil::normalize_slot(slot)).ok_or(ExtractorError::Slot(format!("slot {} of {} hasn't been read", slot, e.fullname())))?;
+        Ok((slot, word))
+    }
+    fn is_bytes_encoded(&self, e: &Executable) -> bool {
+        e.type_kind == TypeKind::Primitive
+            && self.visit_ast(&e.fulltype).is_some_and(|type_info| type_info["encoding"] == "bytes")
+    }
+    fn bytes_payload(&self, slot: &str, word: &str) -> Result<String, String> {
+        let (length, is_long) = ValueDecoder::bytes_length(word)?;
+        if !is_long {
+            return word.get(..length * 2).map(|payload| payload.to_string()).ok_or(format!("{} is shorter than its length {}", word, length));
+        }
+
+        let data_slot = SlotUtil::dynamic_array_data_slot(slot)?;
+        let mut payload = String::new();
+        for i in 0..length.div_ceil(32) {
+            let chunk_slot = SlotUtil::normalize_slot(&SlotUtil::add_to_slot(&data_slot, i)?);
+            match self.words.get(&chunk_slot) {
+                Some(chunk) => payload.push_str(chunk),
+                None => return Err(format!("payload slot {} hasn't been read", chunk_slot)),
+            }
+        }
+        payload.get(..length * 2).map(|payload| payload.to_string()).ok_or(format!("payload of {} is shorter than its length {}", slot, length))
+    }
+    pub fn bulk_save_values(&mut self, executed: &[Executable]) -> &mut Self {
+        for e in executed.iter() {
+            if self.is_skipped(e.id) {
+                continue;
+            }
+            match self.cut_value(e) {
+                Ok(value) => {
+                    self.values.insert(e.id, value);
+                },
+                Err(err) => {
+                    self.skip(e, err);
+                },
+            }
+        };
+        self
+    }
+    fn cut_value(&self, e: &Executable) -> Result<String, ExtractorError> {
+        let (slot, word) = self.slot_word(e)?;
+        if self.is_bytes_encoded(e) {
+            self.bytes_payload(slot, word).map_err(ExtractorError::Slot)
+        } else if e.type_kind == TypeKind::Primitive {
+            SlotUtil::extract_packed(word, e.offset, self.number_of_bytes(&e.fulltype)?).map_err(ExtractorError::Slot)
+        } else {
+            Ok(word.clone())
+        }
+    }
+    pub fn bulk_decode_values(&mut self, executed: &[Executable]) -> &mut Self {
+        for e in executed.iter().filter(|e| e.type_kind == TypeKind::Primitive) {
+            let (value, type_info) = match (self.values.get(&e.id), self.value_type_info(&e.fulltype)) {
+                (Some(value), Some(type_info)) => (value, type_info),
+                _ => continue,
+            };
+            match ValueDecoder::decode(value, &type_info, self.enum_variants.get(&e.fulltype)) {
+                Ok(decoded) => {
+                    self.decoded.insert(e.id, decoded);
+                },
+                Err(err) => eprintln!("{} is kept undecoded: {}", e.fullname(), err),
+            }
         };
         self
     }
@@ -147,8 +325,39 @@ impl Registry {
     }
 
 
+    // Keys to visit for an iterish node, from its filled `from..to` range.
+    pub fn iter_indices(&self, id: usize) -> Vec<String> {
+        match self.iterish_from_to.get(&id) {
+            Some((from, to)) => (*from..*to).map(|i| i.to_string()).collect(),
+            None => Vec::new(),
+        }
+    }
+
     pub fn visit_ast(&self, fulltype: &str) -> Option<Value> {
-        return self.types.get(fulltype).cloned();
+        self.types.get(fulltype).cloned()
+    }
+    // The entry a value is decoded with: a UDVT decodes as its underlying elementary type.
+    fn value_type_info(&self, fulltype: &str) -> Option<Value> {
+        let type_info = self.visit_ast(fulltype)?;
+        match type_info["underlying"].as_str() {
+            Some(underlying) => self.visit_ast(underlying),
+            None => Some(type_info),
+        }
+    }
+    pub fn type_info(&self, fulltype: &str) -> Result<Value, ExtractorError> {
+        self.visit_ast(fulltype).ok_or(ExtractorError::Layout(format!("{} is not in the storageLayout types", fulltype)))
+    }
+    pub fn label(&self, fulltype: &str) -> Result<String, ExtractorError> {
+        match self.type_info(fulltype)?["label"].as_str() {
+            Some(label) => Ok(label.to_string()),
+            None => Err(ExtractorError::Layout(format!("{} has no label", fulltype))),
+        }
+    }
+    pub fn number_of_bytes(&self, fulltype: &str) -> Result<usize, ExtractorError> {
+        match self.type_info(fulltype)?["numberOfBytes"].as_str().and_then(|n| n.parse::<usize>().ok()) {
+            Some(number_of_bytes) => Ok(number_of_bytes),
+            None => Err(ExtractorError::Layout(format!("{} has no numberOfBytes", fulltype))),
+        }
     }
 
 
@@ -0,0 +1,155 @@
+use super::error::ExtractorError;
+
+use serde_json::json;
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+// Requests are GETs without a body, so anything past this is not one of ours.
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+// Read-only HTTP view of an extracted document (see OutputTree::document).
+//   GET /                    -> the whole document
+//   GET /values/Foo/owner    -> the subtree at Foo.owner
+pub struct Server;
+
+impl Server {
+    pub async fn serve(document: Value, addr: &str) -> Result<(), ExtractorError> {
+        let listener = TcpListener::bind(addr).await
+            .map_err(|e| ExtractorError::Config(format!("Failed to bind {}: {}", addr, e)))?;
+        eprintln!("Serving on http://{}", addr);
+
+        let document = Arc::new(document);
+        loop {
+            let (stream, _) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    eprintln!("Failed to accept a connection: {}", err);
+                    continue;
+                },
+            };
+            // Note: a slow client only holds up its own task
+            let document = Arc::clone(&document);
+            tokio::spawn(async move {
+                if let Err(err) = Self::handle(stream, &document).await {
+                    eprintln!("Failed to respond: {}", err);
+                }
+            });
+        }
+    }
+
+    async fn handle<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, document: &Value) -> std::io::Result<()> {
+        let (status, body) = match Self::read_head(&mut stream).await? {
+            Err(status) => (status, json!({ "error": Self::reason(status).to_lowercase() })),
+            Ok(head) => {
+                // Note: only the request line matters, e.g. "GET /values/Foo HTTP/1.1"
+                let mut parts = head.split_whitespace();
+                match (parts.next(), parts.next()) {
+                    (Some("GET"), Some(path)) => Self::respond(document, path),
+                    (Some(_), Some(_)) => (405, json!({ "error": "method not allowed" })),
+                    _ => (400, json!({ "error": "bad request" })),
+                }
+            },
+        };
+        let body = body.to_string();
+        let allow = if status == 405 { "Allow: GET\r\n" } else { "" };
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+            status, Self::reason(status), body.len(), allow, body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+
+    // Read up to the blank line ending the headers; a status to answer with when the head is unusable.
+    async fn read_head<S: AsyncRead + Unpin>(stream: &mut S) -> std::io::Result<Result<String, u16>> {
+        let mut head = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+            if head.len() > MAX_HEAD_BYTES {
+                return Ok(Err(431));
+            }
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(Err(400));
+            }
+            head.extend_from_slice(&buf[..n]);
+        }
+        Ok(Ok(String::from_utf8_lossy(&head).into_owned()))
+    }
+
+    pub fn respond(document: &Value, path: &str) -> (u16, Value) {
+        let path = path.split('?').next().unwrap_or_default().trim_end_matches('/');
+        if path.is_empty() {
+            return (200, document.clone());
+        }
+        let mut segments = path.trim_start_matches('/').split('/');
+        if segments.next() != Some("values") {
+            return (404, json!({ "error": format!("no route for {}", path) }));
+        }
+        let mut node = &document["values"];
+        for segment in segments {
+            node = match node.get(segment) {
+                Some(child) => child,
+                None => return (404, json!({ "error": format!("no value at {}", path) })),
+            };
+        }
+        (200, node.clone())
+    }
+
+    fn reason(status: u16) -> &'static str {
+        match status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            431 => "Request Header Fields Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_respond() {
+        let document = json!({ "block": "latest", "values": { "Foo": { "owner": "0xab", "pairs": { "0": { "x": "1" } } } }, "skipped": [] });
+
+        assert_eq!(Server::respond(&document, "/"), (200, document.clone()));
+        assert_eq!(Server::respond(&document, "/values/Foo/owner"), (200, json!("0xab")));
+        assert_eq!(Server::respond(&document, "/values/Foo/pairs/0/"), (200, json!({ "x": "1" })));
+        assert_eq!(Server::respond(&document, "/values/Foo/missing").0, 404);
+        assert_eq!(Server::respond(&document, "/other").0, 404);
+    }
+
+    async fn exchange(chunks: Vec<&'static str>) -> String {
+        let document = json!({ "values": { "Foo": { "owner": "0xab" } } });
+        let (mut client, server) = tokio::io::duplex(1024);
+        let handler = tokio::spawn(async move { Server::handle(server, &document).await });
+        for chunk in chunks {
+            client.write_all(chunk.as_bytes()).await.unwrap();
+            tokio::task::yield_now().await;
+        }
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        handler.await.unwrap().unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_handle() {
+        // The head arrives in pieces
+        let response = exchange(vec!["GET /values/Foo/owner HTTP/1.1\r\n", "Host: localhost\r\n", "\r\n"]).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\"0xab\""));
+
+        let response = exchange(vec!["POST / HTTP/1.1\r\n\r\n"]).await;
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(response.contains("Allow: GET\r\n"));
+        assert!(exchange(vec!["GET /other HTTP/1.1\r\n\r\n"]).await.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(exchange(vec!["\r\n\r\n"]).await.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
@@ -0,0 +1,95 @@
+use super::block_id::BlockId;
+use super::error::ExtractorError;
+use super::eth_call::EthCall;
+use super::provider::Provider;
+use super::provider::ReadStrategy;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use async_trait::async_trait;
+
+// Executor only talks to storage through this trait, so traversal can run against fixtures.
+#[async_trait(?Send)]
+pub trait SlotReader {
+    async fn verify(&self) -> Result<(), ExtractorError> {
+        Ok(())
+    }
+    async fn resolve_block(&self) -> Result<BlockId, ExtractorError>;
+    // slots: key=ast_id, value=absolute slot. Returns key=ast_id, value=32-byte word without 0x.
+    async fn read_slots(&self, slots: &HashMap<usize, String>, block: &BlockId) -> Result<HashMap<usize, String>, ExtractorError>;
+}
+
+pub fn from_provider(provider: Provider) -> Rc<dyn SlotReader> {
+    match provider.read_strategy {
+        ReadStrategy::CodeOverride => Rc::new(OverrideCallReader { provider }),
+        ReadStrategy::StorageAt => Rc::new(StorageAtReader { provider }),
+    }
+}
+
+pub struct OverrideCallReader {
+    pub provider: Provider,
+}
+
+#[async_trait(?Send)]
+impl SlotReader for OverrideCallReader {
+    async fn verify(&self) -> Result<(), ExtractorError> {
+        self.provider.verify_chain_id().await
+    }
+    async fn resolve_block(&self) -> Result<BlockId, ExtractorError> {
+        self.provider.resolve_block().await
+    }
+    async fn read_slots(&self, slots: &HashMap<usize, String>, block: &BlockId) -> Result<HashMap<usize, String>, ExtractorError> {
+        EthCall::get_values_by_slots(slots, &self.provider, block).await
+    }
+}
+
+pub struct StorageAtReader {
+    pub provider: Provider,
+}
+
+#[async_trait(?Send)]
+impl SlotReader for StorageAtReader {
+    async fn verify(&self) -> Result<(), ExtractorError> {
+        self.provider.verify_chain_id().await
+    }
+    async fn resolve_block(&self) -> Result<BlockId, ExtractorError> {
+        self.provider.resolve_block().await
+    }
+    async fn read_slots(&self, slots: &HashMap<usize, String>, block: &BlockId) -> Result<HashMap<usize, String>, ExtractorError> {
+        EthCall::get_values_by_storage_at(slots, &self.provider, block).await
+    }
+}
+
+// Fixture storage. Unset slots read as zero, like the EVM.
+pub struct MemoryReader {
+    storage: HashMap<String, String>, // key=slot, value=word (both normalized)
+    block: BlockId,
+}
+
+impl MemoryReader {
+    pub fn new(storage: HashMap<String, String>, block: BlockId) -> Self {
+        Self {
+            storage: storage.iter().map(|(slot, value)| (Self::normalize(slot), Self::normalize(value))).collect(),
+            block,
+        }
+    }
+
+    fn normalize(word: &str) -> String {
+        format!("{:0>64}", word.trim_start_matches("0x").to_lowercase())
+    }
+}
+
+#[async_trait(?Send)]
+impl SlotReader for MemoryReader {
+    async fn resolve_block(&self) -> Result<BlockId, ExtractorError> {
+        Ok(self.block.clone())
+    }
+    async fn read_slots(&self, slots: &HashMap<usize, String>, _block: &BlockId) -> Result<HashMap<usize, String>, ExtractorError> {
+        let mut values: HashMap<usize, String> = HashMap::new();
+        for (ast_id, slot) in slots {
+            let value = self.storage.get(&Self::normalize(slot)).cloned().unwrap_or_else(|| "0".repeat(64));
+            values.insert(*ast_id, value);
+        }
+        Ok(values)
+    }
+}
@@ -0,0 +1,262 @@
+use ethers::utils::keccak256;
+use ethers::utils::hex;
+use num_bigint::{BigInt, BigUint, Sign};
+use num_traits::One;
+
+
+#[derive(Clone)]
+pub struct SlotUtil;
+
+impl SlotUtil {
+    // Slot of `mapping[key]` where the mapping itself lives at `parent_slot`.
+    // value-type keys: keccak256(abi.encode(key) . slot), string/bytes keys: keccak256(key_bytes . slot)
+    pub fn mapping_slot(key: &str, key_type: &str, key_bytes: Option<usize>, parent_slot: &str) -> Result<String, String> {
+        let mut preimage = Self::encode_mapping_key(key, key_type, key_bytes)?;
+        preimage.extend_from_slice(&Self::slot_to_bytes(parent_slot)?);
+        Ok(hex::encode(keccak256(&preimage)))
+    }
+
+    // ERC-7201 namespace root: keccak256(abi.encode(uint256(keccak256(id)) - 1)) & ~bytes32(uint256(0xff))
+    pub fn erc7201_slot(namespace_id: &str) -> String {
+        let id_hash = BigUint::from_bytes_be(&keccak256(namespace_id.as_bytes()));
+        // Note: keccak256 of a string is never 0, the subtraction can't underflow
+        let mut slot = keccak256(Self::left_pad(&(id_hash - BigUint::one()).to_bytes_be()));
+        slot[31] = 0;
+        format!("0x{}", hex::encode(slot))
+    }
+
+    // keccak256(p): where the elements of a dynamic array living at `parent_slot` start.
+    pub fn dynamic_array_data_slot(parent_slot: &str) -> Result<String, String> {
+        Ok(hex::encode(keccak256(Self::slot_to_bytes(parent_slot)?)))
+    }
+
+    // (slots from the first element's slot, byte offset in that slot) of `array[index]`.
+    // Elements of 16 bytes or less are packed; larger ones take ceil(numberOfBytes / 32) slots each.
+    pub fn element_position(index: usize, number_of_bytes: usize) -> (usize, usize) {
+        if number_of_bytes > 0 && number_of_bytes <= 16 {
+            let per_slot = 32 / number_of_bytes;
+            (index / per_slot, (index % per_slot) * number_of_bytes)
+        } else {
+            (index * Self::slots_of(number_of_bytes), 0)
+        }
+    }
+
+    pub fn slots_of(number_of_bytes: usize) -> usize {
+        std::cmp::max(1, number_of_bytes.div_ceil(32))
+    }
+
+    pub fn add_to_slot(slot: &str, number: usize) -> Result<String, String> {
+        // Storage slots wrap around at 2^256
+        let modulus = BigUint::one() << 256;
+        let result: BigUint = (BigUint::from_bytes_be(&Self::slot_to_bytes(slot)?) + BigUint::from(number)) % modulus;
+        Ok(hex::encode(Self::left_pad(&result.to_bytes_be())))
+    }
+
+    // `key_bytes` is the numberOfBytes of key_type in the types table; enums need it (uint8, or uint16 past 256 members).
+    pub fn encode_mapping_key(key: &str, key_type: &str, key_bytes: Option<usize>) -> Result<Vec<u8>, String> {
+        let key = key.trim().trim_matches('"');
+        let key_type = key_type.trim_matches(|c| c == '"' || c == '\\');
+
+        if key_type.starts_with("t_string") {
+            Ok(key.as_bytes().to_vec())
+        } else if key_type.starts_with("t_bytes_") {
+            Self::decode_hex(key)
+        } else if key_type.starts_with("t_uint") {
+            let bits = Self::parse_bits(key_type, "t_uint", 256)?;
+            Ok(Self::encode_uint(&Self::parse_uint(key)?, bits)?.to_vec())
+        } else if key_type.starts_with("t_int") {
+            let bits = Self::parse_bits(key_type, "t_int", 256)?;
+            Ok(Self::encode_int(key, bits)?.to_vec())
+        } else if key_type.starts_with("t_enum(") {
+            let key_bytes = key_bytes.ok_or(format!("numberOfBytes of {} is unknown", key_type))?;
+            Ok(Self::encode_uint(&Self::parse_uint(key)?, key_bytes * 8)?.to_vec())
+        } else if key_type.starts_with("t_address") || key_type.starts_with("t_contract(") {
+            let address = Self::decode_hex(key)?;
+            if address.len() != 20 {
+                return Err(format!("Invalid address mapping key: {} (a from..to range can't enumerate address keys)", key));
+            }
+            Ok(Self::left_pad(&address).to_vec())
+        } else if key_type == "t_bool" {
+            match key {
+                "true" | "1" => Ok(Self::left_pad(&[1u8]).to_vec()),
+                "false" | "0" => Ok(Self::left_pad(&[0u8]).to_vec()),
+                _ => Err(format!("Invalid bool mapping key: {}", key)),
+            }
+        } else if key_type.starts_with("t_bytes") {
+            let size = Self::parse_bits(key_type, "t_bytes", 32)?;
+            let bytes = Self::decode_hex(key)?;
+            if bytes.len() > size {
+                return Err(format!("{} doesn't fit in {}", key, key_type));
+            }
+            // bytesN is left-aligned in abi.encode
+            let mut word = [0u8; 32];
+            word[..bytes.len()].copy_from_slice(&bytes);
+            Ok(word.to_vec())
+        } else {
+            Err(format!("Unsupported mapping key type: {}", key_type))
+        }
+    }
+
+    pub fn normalize_slot(slot: &str) -> String {
+        format!("{:0>64}", slot.trim_matches('"').trim_start_matches("0x").to_lowercase())
+    }
+
+    // Bytes of a packed value in its slot word. `offset` counts from the lower-order end, as in storageLayout.
+    pub fn extract_packed(word: &str, offset: usize, number_of_bytes: usize) -> Result<String, String> {
+        let word = Self::normalize_slot(word);
+        if word.len() != 64 || offset + number_of_bytes > 32 {
+            return Err(format!("{} bytes at offset {} don't fit in the word {}", number_of_bytes, offset, word));
+        }
+        let end = (32 - offset) * 2;
+        let start = end - number_of_bytes * 2;
+        Ok(word[start..end].to_string())
+    }
+
+    pub fn slot_to_bytes(slot: &str) -> Result<[u8; 32], String> {
+        let slot = slot.trim_matches('"').trim_start_matches("0x");
+        if slot.len() > 64 {
+            return Err(format!("Invalid slot length. Expected up to 64 characters, got {}", slot.len()));
+        }
+        let bytes = hex::decode(format!("{:0>64}", slot)).map_err(|e| format!("Failed to decode slot: {}", e))?;
+        let mut word = [0u8; 32];
+        word.copy_from_slice(&bytes);
+        Ok(word)
+    }
+
+    // 0x-prefixed slot -> decimal string, the way storageLayout writes slots
+    pub fn slot_to_decimal(slot: &str) -> Result<String, String> {
+        Ok(BigUint::from_bytes_be(&Self::slot_to_bytes(slot)?).to_string())
+    }
+
+    // "t_uint128" -> 128, "t_bytes4" -> 4. A bare prefix falls back to `default`.
+    fn parse_bits(key_type: &str, prefix: &str, default: usize) -> Result<usize, String> {
+        let digits = key_type.trim_start_matches(prefix);
+        if digits.is_empty() {
+            return Ok(default);
+        }
+        digits.parse::<usize>().map_err(|e| format!("Invalid type {}: {}", key_type, e))
+    }
+
+    fn parse_uint(key: &str) -> Result<BigUint, String> {
+        let parsed = match key.strip_prefix("0x") {
+            Some(hex_key) => BigUint::parse_bytes(hex_key.as_bytes(), 16),
+            None => BigUint::parse_bytes(key.as_bytes(), 10),
+        };
+        parsed.ok_or(format!("Invalid unsigned integer mapping key: {}", key))
+    }
+
+    fn encode_uint(value: &BigUint, bits: usize) -> Result<[u8; 32], String> {
+        if value.bits() as usize > bits {
+            return Err(format!("{} doesn't fit in uint{}", value, bits));
+        }
+        Ok(Self::left_pad(&value.to_bytes_be()))
+    }
+
+    // Two's complement, sign-extended to 32 bytes.
+    fn encode_int(key: &str, bits: usize) -> Result<[u8; 32], String> {
+        let value = match key.strip_prefix("0x") {
+            Some(hex_key) => BigInt::parse_bytes(hex_key.as_bytes(), 16),
+            None => BigInt::parse_bytes(key.as_bytes(), 10),
+        }.ok_or(format!("Invalid signed integer mapping key: {}", key))?;
+
+        let limit = BigInt::one() << (bits - 1);
+        if value >= limit || value < -limit {
+            return Err(format!("{} doesn't fit in int{}", value, bits));
+        }
+
+        let bytes = value.to_signed_bytes_be();
+        let fill = if value.sign() == Sign::Minus { 0xffu8 } else { 0u8 };
+        let mut word = [fill; 32];
+        word[32 - bytes.len()..].copy_from_slice(&bytes);
+        Ok(word)
+    }
+
+    fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+        let value = value.trim_start_matches("0x");
+        let padded = if value.len() % 2 == 1 { format!("0{}", value) } else { value.to_string() };
+        hex::decode(padded).map_err(|e| format!("Failed to decode {}: {}", value, e))
+    }
+
+    fn left_pad(bytes: &[u8]) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[32 - bytes.len()..].copy_from_slice(bytes);
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOT_0: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+    #[test]
+    fn test_mapping_slot_for_uint_key() {
+        // keccak256(abi.encode(uint256(0), uint256(0)))
+        assert_eq!(
+            SlotUtil::mapping_slot("0", "t_uint256", Some(32), SLOT_0).unwrap(),
+            "ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5"
+        );
+    }
+
+    #[test]
+    fn test_array_element_slot() {
+        // keccak256(uint256(0)) + 1 * 2
+        let data_slot = SlotUtil::dynamic_array_data_slot(SLOT_0).unwrap();
+        assert_eq!(
+            SlotUtil::add_to_slot(&data_slot, 2).unwrap(),
+            "290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e565"
+        );
+        assert_eq!(SlotUtil::slots_of(32), 1);
+        assert_eq!(SlotUtil::slots_of(33), 2);
+    }
+
+    #[test]
+    fn test_element_position() {
+        assert_eq!(SlotUtil::element_position(3, 32), (3, 0));
+        assert_eq!(SlotUtil::element_position(1, 64), (2, 0));
+        assert_eq!(SlotUtil::element_position(1, 16), (0, 16)); // uint128[]
+        assert_eq!(SlotUtil::element_position(2, 16), (1, 0));
+        assert_eq!(SlotUtil::element_position(33, 1), (1, 1)); // uint8[]
+        assert_eq!(SlotUtil::element_position(2, 20), (2, 0)); // address[] isn't packed
+    }
+
+    #[test]
+    fn test_erc7201_slot() {
+        // The example of EIP-7201
+        assert_eq!(SlotUtil::erc7201_slot("example.main"), "0x183a6125c38840424c4a85fa12bab2ab606c4b6d0e7cc73c0c06ba5300eab500");
+    }
+
+    #[test]
+    fn test_extract_packed() {
+        // bool at offset 20 packed after an address at offset 0
+        let word = format!("{}01{}", "00".repeat(11), "ab".repeat(20));
+        assert_eq!(SlotUtil::extract_packed(&word, 0, 20).unwrap(), "ab".repeat(20));
+        assert_eq!(SlotUtil::extract_packed(&word, 20, 1).unwrap(), "01");
+        assert_eq!(SlotUtil::extract_packed(&word, 0, 32).unwrap(), word);
+        assert!(SlotUtil::extract_packed(&word, 16, 17).is_err());
+    }
+
+    #[test]
+    fn test_encode_mapping_key() {
+        assert_eq!(hex::encode(SlotUtil::encode_mapping_key("-1", "t_int8", None).unwrap()), "ff".repeat(32));
+        assert_eq!(hex::encode(SlotUtil::encode_mapping_key("true", "t_bool", None).unwrap()), format!("{}01", "00".repeat(31)));
+        assert_eq!(
+            hex::encode(SlotUtil::encode_mapping_key("0xdeadbeef", "t_bytes4", None).unwrap()),
+            format!("deadbeef{}", "00".repeat(28))
+        );
+        assert_eq!(
+            hex::encode(SlotUtil::encode_mapping_key("0x00000000000000000000000000000000000000aa", "t_address", None).unwrap()),
+            format!("{}aa", "00".repeat(31))
+        );
+        assert_eq!(SlotUtil::encode_mapping_key("abc", "t_string_memory_ptr", None).unwrap(), b"abc".to_vec());
+        assert_eq!(SlotUtil::encode_mapping_key("0x0102", "t_bytes_memory_ptr", None).unwrap(), vec![1u8, 2u8]);
+        assert!(SlotUtil::encode_mapping_key("256", "t_uint8", None).is_err());
+        assert!(SlotUtil::encode_mapping_key("-129", "t_int8", None).is_err());
+        // An enum key is as wide as the enum: uint8, or uint16 past 256 members
+        assert_eq!(hex::encode(SlotUtil::encode_mapping_key("2", "t_enum(Kind)5", Some(1)).unwrap()), format!("{}02", "00".repeat(31)));
+        assert_eq!(hex::encode(SlotUtil::encode_mapping_key("300", "t_enum(Big)6", Some(2)).unwrap()), format!("{}012c", "00".repeat(30)));
+        assert!(SlotUtil::encode_mapping_key("300", "t_enum(Kind)5", Some(1)).is_err());
+        assert!(SlotUtil::encode_mapping_key("2", "t_enum(Kind)5", None).is_err());
+    }
+}
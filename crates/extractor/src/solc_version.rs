@@ -0,0 +1,199 @@
+use super::mc_repo_fetcher::MCRepoFetcher;
+use super::indexer_config::SolcConfig;
+use super::error::ExtractorError;
+
+use regex::Regex;
+use semver::{Version, VersionReq};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::LazyLock;
+
+static SPACED_OPERATOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(>=|<=|>|<|=|\^|~)\s+").unwrap());
+static PRAGMA: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"pragma\s+solidity\s+([^;]+);").unwrap());
+static SOLC_VERSION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"Version:\s*(\d+\.\d+\.\d+)").unwrap());
+
+// A solidity version constraint: alternatives joined by `||`, each a set of comparators that all must hold.
+#[derive(Clone, Debug)]
+pub struct SolcRequirement {
+    pub constraint: String, // as written, e.g. ">=0.8.20 <0.9.0"
+    pub origin: String,     // where it was read from, for error messages
+    alternatives: Vec<VersionReq>,
+}
+
+impl SolcRequirement {
+    // Note: in solidity a bare version means exactly that version, where semver would read it as ^.
+    pub fn parse(constraint: &str, origin: &str) -> Result<Self, ExtractorError> {
+        let normalized = SPACED_OPERATOR.replace_all(constraint.trim(), "$1").to_string();
+
+        let mut alternatives = Vec::new();
+        for alternative in normalized.split("||") {
+            let comparators: Vec<String> = alternative.split_whitespace()
+                .map(|token| if token.starts_with(|c: char| c.is_ascii_digit()) { format!("={}", token) } else { token.to_string() })
+                .collect();
+            let requirement = VersionReq::parse(&comparators.join(", "))
+                .map_err(|e| ExtractorError::Compile(format!("Invalid solidity version constraint {:?} in {}: {}", constraint, origin, e)))?;
+            alternatives.push(requirement);
+        }
+        Ok(Self { constraint: constraint.trim().to_string(), origin: origin.to_string(), alternatives })
+    }
+
+    pub fn matches(&self, version: &Version) -> bool {
+        self.alternatives.iter().any(|requirement| requirement.matches(version))
+    }
+}
+
+pub struct SolcResolver;
+
+impl SolcResolver {
+    // The solc binary to compile the bundle with.
+    // The constraint is solc.version, else foundry.toml's solc_version, else Schema.sol's pragma.
+    // With solc.cache_dir the newest matching binary there is used; otherwise solc.path has to match.
+    pub fn resolve(fetcher: &MCRepoFetcher, config: &SolcConfig) -> Result<String, ExtractorError> {
+        let requirement = match Self::requirement(fetcher, config)? {
+            Some(requirement) => requirement,
+            None => return Ok(config.path.clone()),
+        };
+
+        match &config.cache_dir {
+            Some(cache_dir) => {
+                let installed = Self::installed(cache_dir)?;
+                match installed.iter().find(|(version, _)| requirement.matches(version)) {
+                    Some((version, path)) => {
+                        eprintln!("Using solc {} for {} ({})", version, requirement.constraint, requirement.origin);
+                        Ok(path.display().to_string())
+                    },
+                    None => {
+                        let versions: Vec<String> = installed.iter().map(|(version, _)| version.to_string()).collect();
+                        Err(ExtractorError::Compile(format!(
+                            "No solc in {} satisfies {} (from {}); installed: {}",
+                            cache_dir.display(), requirement.constraint, requirement.origin,
+                            if versions.is_empty() { "none".to_string() } else { versions.join(", ") }
+                        )))
+                    },
+                }
+            },
+            None => {
+                let version = Self::binary_version(&config.path)?;
+                if requirement.matches(&version) {
+                    Ok(config.path.clone())
+                } else {
+                    Err(ExtractorError::Compile(format!(
+                        "{} is solc {}, but {} needs {}; install a matching version or set solc.cache_dir",
+                        config.path, version, requirement.origin, requirement.constraint
+                    )))
+                }
+            },
+        }
+    }
+
+    pub fn requirement(fetcher: &MCRepoFetcher, config: &SolcConfig) -> Result<Option<SolcRequirement>, ExtractorError> {
+        if let Some(version) = &config.version {
+            return SolcRequirement::parse(version, "solc.version").map(Some);
+        }
+
+        let foundry_toml = fetcher.identifier_path.join("foundry.toml");
+        if let Ok(content) = fs::read_to_string(&foundry_toml) {
+            let foundry: toml::Value = toml::from_str(&content).map_err(|e| ExtractorError::Compile(format!("Failed to parse {}: {}", foundry_toml.display(), e)))?;
+            let default = foundry.get("profile").and_then(|profile| profile.get("default"));
+            // Note: `solc` may also be a path to a binary, only a version counts here
+            let pinned = ["solc_version", "solc"].iter()
+                .filter_map(|key| default.and_then(|default| default.get(*key)).and_then(|value| value.as_str()))
+                .find(|value| Version::parse(value).is_ok());
+            if let Some(version) = pinned {
+                return SolcRequirement::parse(version, &foundry_toml.display().to_string()).map(Some);
+            }
+        }
+
+        let schema_source = fetcher.schema_source()?;
+        let schema_path = fetcher.identifier_path.join(&schema_source);
+        let code = fs::read_to_string(&schema_path).map_err(|e| ExtractorError::Compile(format!("Failed to read {}: {}", schema_path.display(), e)))?;
+        match Self::pragma(&code) {
+            Some(pragma) => SolcRequirement::parse(&pragma, &schema_source).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn pragma(code: &str) -> Option<String> {
+        PRAGMA.captures(code).map(|capture| capture[1].trim().to_string())
+    }
+
+    // Binaries in cache_dir, newest first: solc-<version>, solc-v<version>, or <version>/solc[-<version>] (svm's layout).
+    pub fn installed(cache_dir: &Path) -> Result<Vec<(Version, PathBuf)>, ExtractorError> {
+        let entries = fs::read_dir(cache_dir).map_err(|e| ExtractorError::Compile(format!("Failed to read solc cache {}: {}", cache_dir.display(), e)))?;
+        let mut installed = Vec::new();
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() {
+                if let Ok(version) = Version::parse(name.trim_start_matches('v')) {
+                    let binary = [format!("solc-{}", name), "solc".to_string()].iter().map(|file| path.join(file)).find(|file| file.is_file());
+                    if let Some(binary) = binary {
+                        installed.push((version, binary));
+                    }
+                }
+            } else if let Some(version) = name.strip_prefix("solc-") {
+                if let Ok(version) = Version::parse(version.trim_start_matches('v')) {
+                    installed.push((version, path));
+                }
+            }
+        }
+        installed.sort_by(|a, b| b.0.cmp(&a.0));
+        Ok(installed)
+    }
+
+    pub fn binary_version(solc_path: &str) -> Result<Version, ExtractorError> {
+        let output = Command::new(solc_path).arg("--version").output()
+            .map_err(|e| ExtractorError::Compile(format!("Failed to run {}: {}", solc_path, e)))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        SOLC_VERSION.captures(&stdout)
+            .and_then(|capture| Version::parse(&capture[1]).ok())
+            .ok_or(ExtractorError::Compile(format!("Can't read the version of {} from: {}", solc_path, stdout.trim())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_requirement() {
+        let version = |v: &str| Version::parse(v).unwrap();
+        let caret = SolcRequirement::parse("^0.8.24", "Schema.sol").unwrap();
+        assert!(caret.matches(&version("0.8.26")));
+        assert!(!caret.matches(&version("0.8.19")));
+
+        let exact = SolcRequirement::parse("0.8.24", "Schema.sol").unwrap();
+        assert!(exact.matches(&version("0.8.24")));
+        assert!(!exact.matches(&version("0.8.25")));
+
+        let range = SolcRequirement::parse(">= 0.8.20 <0.8.25 || 0.7.6", "Schema.sol").unwrap();
+        assert!(range.matches(&version("0.8.24")));
+        assert!(range.matches(&version("0.7.6")));
+        assert!(!range.matches(&version("0.8.25")));
+    }
+
+    #[test]
+    fn test_resolve_from_cache() {
+        let cache = tempdir().unwrap().into_path();
+        fs::write(cache.join("solc-0.8.19"), "").unwrap();
+        fs::write(cache.join("solc-v0.8.26"), "").unwrap();
+        fs::create_dir_all(cache.join("0.8.24")).unwrap();
+        fs::write(cache.join("0.8.24/solc-0.8.24"), "").unwrap();
+
+        let (checkout, fetcher) = test_support::checkout("// SPDX-License-Identifier: MIT\npragma solidity >=0.8.20 <0.8.26;\n");
+
+        let mut config = SolcConfig { cache_dir: Some(cache.clone()), ..SolcConfig::default() };
+        assert_eq!(SolcResolver::resolve(&fetcher, &config).unwrap(), cache.join("0.8.24/solc-0.8.24").display().to_string());
+
+        fs::write(checkout.join("foundry.toml"), "[profile.default]\nsolc_version = \"0.8.19\"\n").unwrap();
+        assert_eq!(SolcResolver::resolve(&fetcher, &config).unwrap(), cache.join("solc-0.8.19").display().to_string());
+
+        config.version = Some("0.8.21".to_string());
+        let message = SolcResolver::resolve(&fetcher, &config).unwrap_err().to_string();
+        assert!(message.contains("No solc in"));
+        assert!(message.contains("satisfies 0.8.21 (from solc.version); installed: 0.8.26, 0.8.24, 0.8.19"));
+    }
+}
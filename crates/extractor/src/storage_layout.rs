@@ -0,0 +1,79 @@
+use super::compiler::Compiler;
+use super::ast_layout::ASTLayout;
+use super::mc_repo_fetcher::MCRepoFetcher;
+use super::error::ExtractorError;
+use super::indexer_config::SolcConfig;
+use super::solc_version::SolcResolver;
+
+use std::collections::HashMap;
+use serde_json::Value;
+
+// Everything a scan needs to know about the bundle's storage, independent of any chain.
+#[derive(Clone, Debug)]
+pub struct StorageLayout {
+    pub bundle: String,
+    pub blob: Value, // solc standard JSON output (the ast of every source) + the "storageLayout" ASTLayout built from it
+    pub base_slots: HashMap<String, String>, // key=schema struct name, value=0x-prefixed base slot
+    pub commit: Option<String>, // guest repo commit of the sources, when they're in git
+}
+
+impl StorageLayout {
+    // standard JSON input -> solc (the version Schema.sol asks for) -> base slots from the ast (+ BaseSlots.sol) -> ASTLayout
+    // With solc.artifacts, forge's build output stands in for solc, and its storageLayout types are used when they cover the schema.
+    pub fn compile(fetcher: &MCRepoFetcher, solc: &SolcConfig) -> Result<Self, ExtractorError> {
+        if !fetcher.is_fetched() {
+            return Err(ExtractorError::Fetch(format!("{} isn't fetched yet.", fetcher.identifier_path.display())));
+        }
+        let (mut compiler, mut blob) = match &solc.artifacts {
+            Some(artifacts) => {
+                let compiler = Compiler::from_fetcher(solc.path.clone(), fetcher);
+                (compiler, Compiler::load_artifacts(&fetcher.identifier_path.join(artifacts))?)
+            },
+            None => {
+                let output_cache = solc.output_cache.clone().unwrap_or_else(|| fetcher.local_repo_path.join(".cache").join("solc"));
+                let mut compiler = Compiler::from_fetcher(SolcResolver::resolve(fetcher, solc)?, fetcher).with_cache(Some(output_cache));
+                fetcher.gen_standard_json_input()?;
+                let blob = compiler.prepare_storage_layout()?;
+                (compiler, blob)
+            },
+        };
+
+        let erc7201 = Compiler::erc7201_slots(&blob);
+        // Note: BaseSlots.sol is optional once the schema is annotated
+        let declared = if fetcher.identifier_path.join("src").join(&fetcher.bundle).join("storages/BaseSlots.sol").exists() {
+            compiler.prepare_base_slots()?
+        } else {
+            HashMap::new()
+        };
+        let (base_slots, warnings) = Compiler::reconcile_base_slots(erc7201, declared);
+        for warning in warnings.iter() {
+            eprintln!("Warning: {}", warning);
+        }
+        if base_slots.is_empty() {
+            return Err(ExtractorError::Layout(format!("No base slots for {}: annotate the schema structs with @custom:storage-location erc7201:<id> or declare them in BaseSlots.sol", fetcher.bundle)));
+        }
+
+        blob["storageLayout"] = match Compiler::artifact_layout(&blob, &fetcher.bundle, &base_slots)? {
+            Some(layout) => layout,
+            None if solc.artifacts.is_some() => ASTLayout::storage_layout(&blob, &base_slots).map_err(|e| ExtractorError::Layout(format!(
+                "{}; the forge artifacts have neither a storageLayout nor the ast of the schema structs (set extra_output = [\"storageLayout\"] or ast = true in foundry.toml)", e
+            )))?,
+            None => ASTLayout::storage_layout(&blob, &base_slots)?,
+        };
+        Ok(Self {
+            bundle: fetcher.bundle.clone(),
+            blob,
+            base_slots,
+            commit: fetcher.head_commit(),
+        })
+    }
+
+    // The layout of the schema structs, shaped like solc's storageLayout: {"storage": [...], "types": {...}}
+    pub fn storage_layout(&self) -> &Value {
+        &self.blob["storageLayout"]
+    }
+
+    pub fn types(&self) -> &Value {
+        &self.storage_layout()["types"]
+    }
+}
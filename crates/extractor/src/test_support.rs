@@ -0,0 +1,49 @@
+use super::mc_repo_fetcher::MCRepoFetcher;
+use super::storage_layout::StorageLayout;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use serde_json::{json, Value};
+use tempfile::tempdir;
+
+// Fixtures the unit tests share: a storageLayout types table, the StorageLayout around it and a local checkout.
+
+pub const BASE_SLOT: &str = "0x0000000000000000000000000000000000000000000000000000000000000100";
+
+// The elementary entries of the fixture types, as solc writes them.
+pub fn elementary() -> Value {
+    json!({
+        "t_address": { "encoding": "inplace", "label": "address", "numberOfBytes": "20" },
+        "t_uint128": { "encoding": "inplace", "label": "uint128", "numberOfBytes": "16" },
+        "t_uint256": { "encoding": "inplace", "label": "uint256", "numberOfBytes": "32" }
+    })
+}
+
+// `entries` (structs, mappings, arrays, ...) plus the elementary entries they refer to.
+pub fn types(entries: Value) -> Value {
+    let mut types = elementary();
+    if let (Some(types), Some(entries)) = (types.as_object_mut(), entries.as_object()) {
+        types.extend(entries.clone());
+    }
+    types
+}
+
+// A textDAO layout of `types`; base_slots: (schema struct name, 0x-prefixed base slot).
+pub fn layout(types: Value, base_slots: &[(&str, &str)]) -> StorageLayout {
+    StorageLayout {
+        bundle: "textDAO".to_string(),
+        blob: json!({ "storageLayout": { "types": types } }),
+        base_slots: base_slots.iter().map(|(name, slot)| (name.to_string(), slot.to_string())).collect::<HashMap<_, _>>(),
+        commit: None,
+    }
+}
+
+// A throwaway textDAO checkout whose Schema.sol holds `schema`, and a local fetcher of it.
+pub fn checkout(schema: &str) -> (PathBuf, MCRepoFetcher) {
+    let checkout = tempdir().unwrap().into_path();
+    fs::create_dir_all(checkout.join("src/textDAO/storages")).unwrap();
+    fs::write(checkout.join("src/textDAO/storages/Schema.sol"), schema).unwrap();
+    let fetcher = MCRepoFetcher::local(checkout.clone(), "textDAO".to_string(), tempdir().unwrap().into_path(), ".repo").unwrap();
+    (checkout, fetcher)
+}
@@ -0,0 +1,270 @@
+use super::type_kind::TypeKind;
+
+use std::error::Error;
+use std::fmt;
+
+// Data location suffix of a solc type identifier: `_storage`, `_memory_ptr`, ...
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Location {
+    Storage,
+    StoragePtr,
+    Memory,
+    MemoryPtr,
+    Calldata,
+    CalldataPtr,
+}
+
+impl Location {
+    // Longest suffixes first, so `_storage_ptr` isn't taken for `_storage`.
+    const SUFFIXES: [(&'static str, Location); 6] = [
+        ("_storage_ptr", Location::StoragePtr),
+        ("_storage", Location::Storage),
+        ("_memory_ptr", Location::MemoryPtr),
+        ("_memory", Location::Memory),
+        ("_calldata_ptr", Location::CalldataPtr),
+        ("_calldata", Location::Calldata),
+    ];
+
+    fn suffix(&self) -> &'static str {
+        Self::SUFFIXES.iter().find(|(_, location)| location == self).unwrap().0
+    }
+}
+
+// Typed AST of a solc storage type identifier, e.g. t_mapping(t_address,t_struct(Foo)12_storage)
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeId {
+    Elementary { name: String, location: Option<Location> }, // uint256, address_payable, string, bytes, ...
+    Mapping { key: Box<TypeId>, value: Box<TypeId> },
+    Array { base: Box<TypeId>, length: Option<usize>, location: Option<Location> }, // length None = dyn
+    Struct { name: String, ast_id: usize, location: Option<Location> },
+    Enum { name: String, ast_id: usize },
+    Contract { name: String, ast_id: usize },
+    UserDefinedValueType { name: String, ast_id: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeIdError {
+    pub input: String,
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TypeIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {} in type identifier {}", self.message, self.position, self.input)
+    }
+}
+
+impl Error for TypeIdError {}
+
+impl TypeId {
+    pub fn parse(input: &str) -> Result<TypeId, TypeIdError> {
+        let mut parser = TypeIdParser { input, position: 0 };
+        let parsed = parser.parse_type()?;
+        if parser.position != input.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(parsed)
+    }
+
+    pub fn type_kind(&self) -> TypeKind {
+        match self {
+            TypeId::Mapping { .. } => TypeKind::Mapping,
+            TypeId::Array { length: None, .. } => TypeKind::Array,
+            TypeId::Array { length: Some(length), .. } => TypeKind::FixedArray(*length),
+            TypeId::Struct { .. } => TypeKind::NaiveStruct,
+            _ => TypeKind::Primitive,
+        }
+    }
+}
+
+// Prints the identifier back in solc's format.
+impl fmt::Display for TypeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let suffix = |location: &Option<Location>| location.map(|l| l.suffix()).unwrap_or("");
+        match self {
+            TypeId::Elementary { name, location } => write!(f, "t_{}{}", name, suffix(location)),
+            TypeId::Mapping { key, value } => write!(f, "t_mapping({},{})", key, value),
+            TypeId::Array { base, length: Some(length), location } => write!(f, "t_array({}){}{}", base, length, suffix(location)),
+            TypeId::Array { base, length: None, location } => write!(f, "t_array({})dyn{}", base, suffix(location)),
+            TypeId::Struct { name, ast_id, location } => write!(f, "t_struct({}){}{}", name, ast_id, suffix(location)),
+            TypeId::Enum { name, ast_id } => write!(f, "t_enum({}){}", name, ast_id),
+            TypeId::Contract { name, ast_id } => write!(f, "t_contract({}){}", name, ast_id),
+            TypeId::UserDefinedValueType { name, ast_id } => write!(f, "t_userDefinedValueType({}){}", name, ast_id),
+        }
+    }
+}
+
+struct TypeIdParser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> TypeIdParser<'a> {
+    // type := "t_" ( mapping | array | named | elementary )
+    fn parse_type(&mut self) -> Result<TypeId, TypeIdError> {
+        self.expect("t_")?;
+        let head = self.read_word();
+
+        match head {
+            "mapping" => {
+                self.expect("(")?;
+                let key = self.parse_type()?;
+                self.expect(",")?;
+                let value = self.parse_type()?;
+                self.expect(")")?;
+                Ok(TypeId::Mapping { key: Box::new(key), value: Box::new(value) })
+            },
+            "array" => {
+                self.expect("(")?;
+                let base = self.parse_type()?;
+                self.expect(")")?;
+                // <length>|dyn, then an optional location
+                let tail_position = self.position;
+                let (length, location) = Self::split_location(self.read_word());
+                let length = match length {
+                    "dyn" => None,
+                    _ => Some(length.parse::<usize>().map_err(|_| self.error_at(tail_position, "expected an array length or dyn"))?),
+                };
+                Ok(TypeId::Array { base: Box::new(base), length, location })
+            },
+            "struct" | "enum" | "contract" | "userDefinedValueType" => {
+                self.expect("(")?;
+                let name = self.read_until(')');
+                if name.is_empty() {
+                    return Err(self.error("expected a name"));
+                }
+                self.expect(")")?;
+                let tail_position = self.position;
+                let (ast_id, location) = Self::split_location(self.read_word());
+                let ast_id = ast_id.parse::<usize>().map_err(|_| self.error_at(tail_position, "expected an AST id"))?;
+                let name = name.to_string();
+
+                match head {
+                    "struct" => Ok(TypeId::Struct { name, ast_id, location }),
+                    _ if location.is_some() => Err(self.error_at(tail_position, &format!("{} can't have a data location", head))),
+                    "enum" => Ok(TypeId::Enum { name, ast_id }),
+                    "contract" => Ok(TypeId::Contract { name, ast_id }),
+                    _ => Ok(TypeId::UserDefinedValueType { name, ast_id }),
+                }
+            },
+            "" => Err(self.error("expected a type name")),
+            _ => {
+                let word_position = self.position - head.len();
+                let (name, location) = Self::split_location(head);
+                if !Self::is_elementary(name) {
+                    return Err(self.error_at(word_position, &format!("unknown type {}", name)));
+                }
+                Ok(TypeId::Elementary { name: name.to_string(), location })
+            },
+        }
+    }
+
+    fn is_elementary(name: &str) -> bool {
+        let sized = |prefix: &str| match name.strip_prefix(prefix) {
+            Some(bits) => bits.is_empty() || bits.chars().all(|c| c.is_ascii_digit()),
+            None => false,
+        };
+        matches!(name, "address" | "address_payable" | "bool" | "string" | "bytes")
+            || sized("uint") || sized("int") || sized("bytes")
+    }
+
+    // "3_storage" -> ("3", Some(Storage)), "uint256" -> ("uint256", None)
+    fn split_location(word: &str) -> (&str, Option<Location>) {
+        for (suffix, location) in Location::SUFFIXES.iter() {
+            if let Some(rest) = word.strip_suffix(suffix) {
+                return (rest, Some(*location));
+            }
+        }
+        (word, None)
+    }
+
+    // A word is an identifier-like run, so it may carry a location suffix.
+    fn read_word(&mut self) -> &'a str {
+        let input = self.input;
+        let rest = &input[self.position..];
+        let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+
+    fn read_until(&mut self, delimiter: char) -> &'a str {
+        let input = self.input;
+        let rest = &input[self.position..];
+        let end = rest.find(delimiter).unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), TypeIdError> {
+        if self.input[self.position..].starts_with(token) {
+            self.position += token.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", token)))
+        }
+    }
+
+    fn error(&self, message: &str) -> TypeIdError {
+        self.error_at(self.position, message)
+    }
+
+    fn error_at(&self, position: usize, message: &str) -> TypeIdError {
+        TypeIdError {
+            input: self.input.to_string(),
+            position,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(input: &str) -> TypeId {
+        let parsed = TypeId::parse(input).unwrap();
+        assert_eq!(parsed.to_string(), input);
+        parsed
+    }
+
+    #[test]
+    fn test_parse_nested_mapping() {
+        let parsed = roundtrip("t_mapping(t_address,t_mapping(t_uint256,t_struct(Foo)12_storage))");
+        match parsed {
+            TypeId::Mapping { key, value } => {
+                assert_eq!(*key, TypeId::Elementary { name: "address".to_string(), location: None });
+                assert_eq!(value.to_string(), "t_mapping(t_uint256,t_struct(Foo)12_storage)");
+                assert_eq!(value.type_kind(), TypeKind::Mapping);
+            },
+            _ => panic!("not a mapping: {:?}", parsed),
+        }
+    }
+
+    #[test]
+    fn test_parse_arrays_and_named_types() {
+        assert_eq!(roundtrip("t_array(t_uint256)3_storage").type_kind(), TypeKind::FixedArray(3));
+        assert_eq!(roundtrip("t_array(t_mapping(t_bytes32,t_bool))dyn_storage").type_kind(), TypeKind::Array);
+        assert_eq!(roundtrip("t_array(t_array(t_uint8)2_storage)dyn_storage_ptr").type_kind(), TypeKind::Array);
+        assert_eq!(roundtrip("t_struct(Proposal)123_storage_ptr").type_kind(), TypeKind::NaiveStruct);
+        assert_eq!(roundtrip("t_enum(Status)45").type_kind(), TypeKind::Primitive);
+        assert_eq!(roundtrip("t_contract(IERC20)67").type_kind(), TypeKind::Primitive);
+        assert_eq!(roundtrip("t_userDefinedValueType(Price)89").type_kind(), TypeKind::Primitive);
+        assert_eq!(
+            roundtrip("t_string_memory_ptr"),
+            TypeId::Elementary { name: "string".to_string(), location: Some(Location::MemoryPtr) }
+        );
+        roundtrip("t_address_payable");
+        roundtrip("t_bytes_storage");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(TypeId::parse("t_mapping(t_address t_uint256)").unwrap_err().position, 19);
+        assert!(TypeId::parse("uint256").is_err());
+        assert!(TypeId::parse("t_array(t_uint256)x_storage").is_err());
+        assert!(TypeId::parse("t_struct(Foo)_storage").is_err());
+        assert!(TypeId::parse("t_function_internal_nonpayable").is_err());
+        assert!(TypeId::parse("t_uint256)").is_err());
+    }
+}
@@ -1,14 +1,15 @@
 #[derive(PartialEq, Clone, Debug)]
 pub enum TypeKind {
     Mapping,
-    Array,
+    Array, // dynamic array, its slot holds the length
+    FixedArray(usize), // static length from the type id, elements inline from the base slot
     NaiveStruct,
     Primitive,
 }
 impl TypeKind {
     pub fn is_iterish(&self) -> bool {
         match self {
-            TypeKind::Mapping | TypeKind::Array => true,
+            TypeKind::Mapping | TypeKind::Array | TypeKind::FixedArray(_) => true,
             TypeKind::NaiveStruct | TypeKind::Primitive => false,
         }
     }
@@ -0,0 +1,168 @@
+use ethers::types::Address;
+use ethers::utils::hex;
+use ethers::utils::to_checksum;
+use num_bigint::{BigInt, BigUint};
+use num_traits::ToPrimitive;
+use serde_json::Value;
+use std::fmt;
+
+// Refuse to chase obviously corrupt lengths of long string/bytes values.
+const MAX_BYTES_LENGTH: usize = 1 << 20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypedValue {
+    Uint(BigUint),
+    Int(BigInt),
+    Bool(bool),
+    Address(String), // EIP-55 checksummed
+    FixedBytes(String), // 0x-prefixed, exactly N bytes
+    Enum { index: usize, name: Option<String> },
+    String(String),
+    Bytes(String), // 0x-prefixed dynamic bytes
+}
+
+impl fmt::Display for TypedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypedValue::Uint(value) => write!(f, "{}", value),
+            TypedValue::Int(value) => write!(f, "{}", value),
+            TypedValue::Bool(value) => write!(f, "{}", value),
+            TypedValue::Address(value) => write!(f, "{}", value),
+            TypedValue::FixedBytes(value) => write!(f, "{}", value),
+            TypedValue::Enum { index, name: Some(name) } => write!(f, "{}({})", name, index),
+            TypedValue::Enum { index, name: None } => write!(f, "{}", index),
+            TypedValue::String(value) => write!(f, "{}", value),
+            TypedValue::Bytes(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+pub struct ValueDecoder;
+
+impl ValueDecoder {
+    // value: the primitive's own bytes (already cut out of its slot word), hex without 0x.
+    // type_info: its entry of the storageLayout `types` table.
+    // enum_variants: member names of the enum definition, when the AST is available.
+    // For the "bytes" encoding, value is the reassembled payload.
+    pub fn decode(value: &str, type_info: &Value, enum_variants: Option<&Vec<String>>) -> Result<TypedValue, String> {
+        let label = type_info["label"].as_str().ok_or("type has no label")?;
+        let encoding = type_info["encoding"].as_str().unwrap_or("inplace");
+
+        let bytes = hex::decode(value.trim_start_matches("0x")).map_err(|e| format!("Failed to decode {}: {}", value, e))?;
+
+        if encoding == "bytes" {
+            return match label {
+                "string" => match String::from_utf8(bytes) {
+                    Ok(text) => Ok(TypedValue::String(text)),
+                    // Not every stored string is valid UTF-8; keep the raw payload then.
+                    Err(err) => Ok(TypedValue::Bytes(format!("0x{}", hex::encode(err.into_bytes())))),
+                },
+                _ => Ok(TypedValue::Bytes(format!("0x{}", hex::encode(&bytes)))),
+            };
+        } else if encoding != "inplace" {
+            return Err(format!("{} is not an inplace value ({} encoding)", label, encoding));
+        }
+
+        if label.starts_with("uint") {
+            Ok(TypedValue::Uint(BigUint::from_bytes_be(&bytes)))
+        } else if label.starts_with("int") {
+            // Two's complement over the type's own width
+            Ok(TypedValue::Int(BigInt::from_signed_bytes_be(&bytes)))
+        } else if label == "bool" {
+            Ok(TypedValue::Bool(bytes.iter().any(|b| *b != 0)))
+        } else if label.starts_with("address") || label.starts_with("contract ") {
+            if bytes.len() < 20 {
+                return Err(format!("{} needs 20 bytes, got {}", label, bytes.len()));
+            }
+            let address = Address::from_slice(&bytes[bytes.len() - 20..]);
+            Ok(TypedValue::Address(to_checksum(&address, None)))
+        } else if label.starts_with("bytes") {
+            Ok(TypedValue::FixedBytes(format!("0x{}", hex::encode(&bytes))))
+        } else if label.starts_with("enum ") {
+            let index = BigUint::from_bytes_be(&bytes).to_usize().ok_or(format!("Invalid enum index: {}", value))?;
+            let name = enum_variants.and_then(|variants| variants.get(index).cloned());
+            Ok(TypedValue::Enum { index, name })
+        } else {
+            Err(format!("Unsupported value type: {}", label))
+        }
+    }
+
+    // Length of a "bytes"-encoded value and whether its data lives outside the slot.
+    // short (< 32 bytes): data left-aligned in the slot, len * 2 in the lowest byte
+    // long: the slot holds len * 2 + 1, data starts at keccak256(slot)
+    pub fn bytes_length(word: &str) -> Result<(usize, bool), String> {
+        let word = format!("{:0>64}", word.trim_start_matches("0x"));
+        let lowest = u8::from_str_radix(&word[62..64], 16).map_err(|e| format!("Failed to decode {}: {}", word, e))?;
+        if lowest & 1 == 0 {
+            return Ok(((lowest / 2) as usize, false));
+        }
+
+        let length = BigUint::parse_bytes(word.as_bytes(), 16)
+            .and_then(|encoded| (encoded >> 1u32).to_usize())
+            .ok_or(format!("Invalid bytes length: {}", word))?;
+        if length > MAX_BYTES_LENGTH {
+            return Err(format!("bytes length {} exceeds {}", length, MAX_BYTES_LENGTH));
+        }
+        Ok((length, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn decode(value: &str, label: &str) -> TypedValue {
+        ValueDecoder::decode(value, &json!({ "encoding": "inplace", "label": label }), None).unwrap()
+    }
+
+    #[test]
+    fn test_decode_integers() {
+        assert_eq!(decode(&"ff".repeat(32), "uint256").to_string(), "115792089237316195423570985008687907853269984665640564039457584007913129639935");
+        assert_eq!(decode("ff", "int8"), TypedValue::Int(BigInt::from(-1)));
+        assert_eq!(decode("ff80", "int16"), TypedValue::Int(BigInt::from(-128)));
+        assert_eq!(decode("7f", "int8"), TypedValue::Int(BigInt::from(127)));
+    }
+
+    #[test]
+    fn test_decode_address_bool_bytes() {
+        assert_eq!(
+            decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed", "address"),
+            TypedValue::Address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed".to_string())
+        );
+        assert_eq!(decode("01", "bool"), TypedValue::Bool(true));
+        assert_eq!(decode("00", "bool"), TypedValue::Bool(false));
+        assert_eq!(decode("deadbeef", "bytes4"), TypedValue::FixedBytes("0xdeadbeef".to_string()));
+    }
+
+    #[test]
+    fn test_decode_bytes_encoding() {
+        let string_type = json!({ "encoding": "bytes", "label": "string" });
+        assert_eq!(
+            ValueDecoder::decode(&hex::encode("hello"), &string_type, None).unwrap(),
+            TypedValue::String("hello".to_string())
+        );
+        let bytes_type = json!({ "encoding": "bytes", "label": "bytes" });
+        assert_eq!(ValueDecoder::decode("0102", &bytes_type, None).unwrap(), TypedValue::Bytes("0x0102".to_string()));
+
+        // "hello" inlined: data left-aligned, 5 * 2 in the lowest byte
+        let short = format!("{}{}0a", hex::encode("hello"), "00".repeat(26));
+        assert_eq!(ValueDecoder::bytes_length(&short).unwrap(), (5, false));
+        // 100 bytes stored out of slot: 100 * 2 + 1
+        assert_eq!(ValueDecoder::bytes_length(&format!("{:0>64x}", 201)).unwrap(), (100, true));
+    }
+
+    #[test]
+    fn test_decode_enum() {
+        let type_info = json!({ "encoding": "inplace", "label": "enum Schema.Status" });
+        let variants = vec!["Pending".to_string(), "Approved".to_string()];
+        assert_eq!(
+            ValueDecoder::decode("01", &type_info, Some(&variants)).unwrap(),
+            TypedValue::Enum { index: 1, name: Some("Approved".to_string()) }
+        );
+        assert_eq!(
+            ValueDecoder::decode("05", &type_info, Some(&variants)).unwrap(),
+            TypedValue::Enum { index: 5, name: None }
+        );
+    }
+}